@@ -1,6 +1,14 @@
-use std::io::*;
+use std::{fs::File, io::*, path::Path};
 
 fn main() {
+    // Usage: day_08 [--export <dir>] [--ramp] <input
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let export_dir = args
+        .iter()
+        .position(|a| a == "--export")
+        .map(|k| args.get(k + 1).expect("--export needs a directory").clone());
+    let ramp = args.iter().any(|a| a == "--ramp");
+
     let fld: Vec<Vec<u8>> = stdin().lines().map(|rs| rs.unwrap().into_bytes()).collect();
     let n = fld.len();
     let m = fld[0].len();
@@ -8,112 +16,189 @@ fn main() {
 
     eprintln!("{n} x {m}");
 
-    let mut visible = vec![vec![false; m]; n];
-
-    let mut visit = |i: usize, j: usize, tallest: &mut Option<u8>| {
-        if tallest.is_none() || fld[i][j] > tallest.unwrap() {
-            visible[i][j] = true;
-            *tallest = Some(fld[i][j]);
-        }
-    };
-
-    for i in 0..n {
-        let mut tallest = None;
-        for j in 0..m {
-            visit(i, j, &mut tallest);
-        }
+    let visible = visibility_map(&fld);
+    let scores = scenic_scores(&fld);
 
-        tallest = None;
-        for j in (0..m).into_iter().rev() {
-            visit(i, j, &mut tallest);
-        }
+    if let Some(dir) = export_dir {
+        export(Path::new(&dir), &visible, &scores, ramp).unwrap();
     }
 
-    for j in 0..m {
-        let mut tallest = None;
-        for i in 0..n {
-            visit(i, j, &mut tallest);
-        }
-
-        tallest = None;
-        for i in (0..n).into_iter().rev() {
-            visit(i, j, &mut tallest);
-        }
-    }
-
-    // for i in 0..n {
-    //     for j in 0..m {
-    //         print!("{}", visible[i][j] as u8);
-    //     }
-    //     println!();
-    // }
-
     let ans = visible
         .into_iter()
         .map(|row| row.into_iter().map(|v| v as u64).sum::<u64>())
         .sum::<u64>();
     
     println!("{ans}");
-    println!("{}", max_scenic_score(&fld));
+
+    let max_score = scores.iter().flatten().copied().max().unwrap();
+    println!("{max_score}");
 }
 
-fn max_scenic_score(fld: &[Vec<u8>]) -> u64 {
+fn visibility_map(fld: &[Vec<u8>]) -> Vec<Vec<bool>> {
     let n = fld.len();
     let m = fld[0].len();
 
-    let mut ans = 0;
-    for y in 0..n {
-        for x in 0..m {
-            let score = scenic_score(fld, y, x);
-            ans = ans.max(score);
+    let mut visible = vec![vec![false; m]; n];
+    sweep(fld, |i, j, _, reaches_edge| {
+        if reaches_edge {
+            visible[i][j] = true;
         }
-    }
+    });
 
-    ans
+    visible
 }
 
-fn scenic_score(fld: &[Vec<u8>], y: usize, x: usize) -> u64 {
+fn scenic_scores(fld: &[Vec<u8>]) -> Vec<Vec<u64>> {
     let n = fld.len();
     let m = fld[0].len();
-    let height = fld[y][x];
 
-    let mut top = 0_u64;
-    for i in (0..y).into_iter().rev() {
-        top += 1;
+    let mut scores = vec![vec![1_u64; m]; n];
+    sweep(fld, |i, j, distance, _| {
+        scores[i][j] *= distance as u64;
+    });
 
-        if fld[i][x] >= height {
-            break;
-        }
+    scores
+}
+
+/// Looks along every row and column in both directions. For each tree `on_tree` receives
+/// the viewing distance and whether the view reaches the edge of the forest.
+fn sweep(fld: &[Vec<u8>], mut on_tree: impl FnMut(usize, usize, usize, bool)) {
+    let n = fld.len();
+    let m = fld[0].len();
+    let mut stack = Vec::with_capacity(n.max(m));
+
+    for i in 0..n {
+        walk_line(fld, (0..m).map(|j| (i, j)), &mut stack, &mut on_tree);
+        walk_line(fld, (0..m).rev().map(|j| (i, j)), &mut stack, &mut on_tree);
+    }
+
+    for j in 0..m {
+        walk_line(fld, (0..n).map(|i| (i, j)), &mut stack, &mut on_tree);
+        walk_line(fld, (0..n).rev().map(|i| (i, j)), &mut stack, &mut on_tree);
     }
+}
 
-    let mut bottom = 0_u64;
-    for i in (y + 1)..n {
-        bottom += 1;
+/// Keeps a stack of trees with non-increasing heights: anything shorter than the
+/// current tree can never block the view of the trees that follow it, while a tree
+/// of equal height stays and is where the current tree's view ends.
+fn walk_line(
+    fld: &[Vec<u8>],
+    line: impl Iterator<Item = (usize, usize)>,
+    stack: &mut Vec<(usize, u8)>,
+    on_tree: &mut impl FnMut(usize, usize, usize, bool),
+) {
+    stack.clear();
+
+    for (k, (i, j)) in line.enumerate() {
+        let height = fld[i][j];
+        while matches!(stack.last(), Some(&(_, h)) if h < height) {
+            stack.pop();
+        }
 
-        if fld[i][x] >= height {
-            break;
+        match stack.last() {
+            Some(&(pos, _)) => on_tree(i, j, k - pos, false),
+            None => on_tree(i, j, k, true),
         }
+        stack.push((k, height));
     }
+}
 
-    let mut left = 0_u64;
-    for j in (0..x).into_iter().rev() {
-        left += 1;
+/// Writes `visible.pgm`, `visible.csv`, `scenic.ppm` and `scenic.csv` into `dir`.
+/// The tree with the best scenic score is painted red on the score image.
+fn export(dir: &Path, visible: &[Vec<bool>], scores: &[Vec<u64>], ramp: bool) -> Result<()> {
+    std::fs::create_dir_all(dir)?;
 
-        if fld[y][j] >= height {
-            break;
-        }
+    let (best_i, best_j) = argmax(scores);
+    let max_score = scores[best_i][best_j];
+    eprintln!("Best tree is at row {best_i}, column {best_j} (score {max_score}).");
+
+    let mut out = BufWriter::new(File::create(dir.join("visible.pgm"))?);
+    writeln!(out, "P2\n{} {}\n1", visible[0].len(), visible.len())?;
+    for row in visible {
+        write_row(&mut out, row.iter().map(|&v| v as u8), " ")?;
     }
+    out.flush()?;
 
-    let mut right = 0_u64;
-    for j in (x + 1)..m {
-        right += 1;
+    let mut out = BufWriter::new(File::create(dir.join("visible.csv"))?);
+    for row in visible {
+        write_row(&mut out, row.iter().map(|&v| v as u8), ",")?;
+    }
+    out.flush()?;
+
+    let mut out = BufWriter::new(File::create(dir.join("scenic.ppm"))?);
+    writeln!(out, "P3\n{} {}\n255", scores[0].len(), scores.len())?;
+    for (i, row) in scores.iter().enumerate() {
+        let pixels = row.iter().enumerate().map(|(j, &score)| {
+            let (r, g, b) = if (i, j) == (best_i, best_j) {
+                (255, 0, 0)
+            } else {
+                let t = if max_score > 0 {
+                    score as f64 / max_score as f64
+                } else {
+                    0.0
+                };
+                if ramp {
+                    colour_ramp(t)
+                } else {
+                    let v = (t * 255.0).round() as u8;
+                    (v, v, v)
+                }
+            };
+            format!("{r} {g} {b}")
+        });
+        write_row(&mut out, pixels, "  ")?;
+    }
+    out.flush()?;
+
+    let mut out = BufWriter::new(File::create(dir.join("scenic.csv"))?);
+    for row in scores {
+        write_row(&mut out, row.iter(), ",")?;
+    }
+    out.flush()
+}
 
-        if fld[y][j] >= height {
-            break;
+fn write_row<T: std::fmt::Display>(
+    out: &mut impl Write,
+    row: impl Iterator<Item = T>,
+    sep: &str,
+) -> Result<()> {
+    for (k, value) in row.enumerate() {
+        if k > 0 {
+            write!(out, "{sep}")?;
         }
+        write!(out, "{value}")?;
     }
+    writeln!(out)
+}
+
+fn argmax(scores: &[Vec<u64>]) -> (usize, usize) {
+    let mut best = (0, 0);
+    for (i, row) in scores.iter().enumerate() {
+        for (j, &score) in row.iter().enumerate() {
+            if score > scores[best.0][best.1] {
+                best = (i, j);
+            }
+        }
+    }
+    best
+}
 
-    right * top * left * bottom
+/// Maps `t` in `[0, 1]` onto a dark blue -> teal -> yellow ramp.
+/// Stays clear of pure red, which marks the best tree.
+fn colour_ramp(t: f64) -> (u8, u8, u8) {
+    const STOPS: [(f64, f64, f64); 3] = [
+        (20.0, 20.0, 90.0),
+        (30.0, 150.0, 140.0),
+        (250.0, 230.0, 40.0),
+    ];
+
+    let t = t.clamp(0.0, 1.0) * (STOPS.len() - 1) as f64;
+    let k = (t.floor() as usize).min(STOPS.len() - 2);
+    let f = t - k as f64;
+    let (a, b) = (STOPS[k], STOPS[k + 1]);
+    let mix = |x: f64, y: f64| (x + (y - x) * f).round() as u8;
+
+    (mix(a.0, b.0), mix(a.1, b.1), mix(a.2, b.2))
 }
 
 fn scan<T: std::str::FromStr>() -> T {
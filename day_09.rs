@@ -1,4 +1,4 @@
-use std::{collections::HashSet, io::*};
+use std::{collections::HashSet, io::*, path::Path};
 
 #[derive(Debug, Clone, Copy, Eq, PartialEq, Default, Hash)]
 struct Loc {
@@ -12,15 +12,88 @@ impl Loc {
     }
 }
 
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+enum FrameRate {
+    PerStep,
+    PerInstr,
+}
+
 fn main() {
-    const ROPE_LEN: usize = 10;
-    let mut visited = HashSet::<Loc>::new();
-    let mut rope = vec![Loc::default(); ROPE_LEN];
+    // Usage: day_09 [--frames <dir> [--per-step]] [rope length] [tracked knot]... <input
+    let mut frames_dir = None;
+    let mut frame_rate = FrameRate::PerInstr;
+    let mut args = Vec::<usize>::new();
+
+    let mut raw_args = std::env::args().skip(1);
+    while let Some(arg) = raw_args.next() {
+        match arg.as_str() {
+            "--frames" => frames_dir = Some(raw_args.next().expect("--frames needs a directory")),
+            "--per-step" => frame_rate = FrameRate::PerStep,
+            _ => args.push(
+                arg.parse()
+                    .expect("Arguments must be non-negative integers"),
+            ),
+        }
+    }
+
+    let rope_len = args.first().copied().unwrap_or(10);
+    assert!(rope_len >= 1, "Rope needs at least one knot");
 
+    let mut tracked = if args.len() > 1 {
+        args[1..].to_vec()
+    } else {
+        vec![1.min(rope_len - 1), rope_len - 1]
+    };
+    tracked.sort();
+    tracked.dedup();
+    assert!(
+        tracked.iter().all(|&k| k < rope_len),
+        "Tracked knots must be less than {rope_len}"
+    );
+
+    let mut moves = Vec::new();
     while let Some(line) = gets() {
         let line: Vec<_> = line.split_whitespace().collect();
         let direction: char = line[0].parse().unwrap();
         let step_count: usize = line[1].parse().unwrap();
+        moves.push((direction, step_count));
+    }
+
+    let mut history = Vec::new();
+    let visited = simulate(&moves, rope_len, &tracked, |rope, instr, last| {
+        if frames_dir.is_some() {
+            history.push((rope.to_vec(), instr, last));
+        }
+    });
+
+    for (k, cells) in tracked.iter().zip(&visited) {
+        println!("Knot #{k} visited {} positions.", cells.len());
+    }
+
+    if let Some(dir) = frames_dir {
+        let count = write_frames(Path::new(&dir), &moves, &history, frame_rate).unwrap();
+        eprintln!("Wrote {count} frames to {dir}.");
+    }
+}
+
+/// Drags a rope of `rope_len` knots along `moves` once, recording the cells
+/// visited by each of the `tracked` knots (in the same order).
+/// After every step `on_step` gets the rope, the index of the current move
+/// and whether this was the move's last step.
+fn simulate(
+    moves: &[(char, usize)],
+    rope_len: usize,
+    tracked: &[usize],
+    mut on_step: impl FnMut(&[Loc], usize, bool),
+) -> Vec<HashSet<Loc>> {
+    let mut visited = vec![HashSet::<Loc>::new(); tracked.len()];
+    let mut rope = vec![Loc::default(); rope_len];
+
+    for (cells, &k) in visited.iter_mut().zip(tracked) {
+        cells.insert(rope[k]);
+    }
+
+    for (instr, &(direction, step_count)) in moves.iter().enumerate() {
         // eprintln!("> {direction:?} {step_count}");
 
         let (dj, di) = match direction {
@@ -31,21 +104,23 @@ fn main() {
             _ => panic!("Unexpected direction {direction:?}!"),
         };
 
-        for _ in 0..step_count {
+        for step in 0..step_count {
             rope[0].i += di;
             rope[0].j += dj;
 
-            for k in 1..ROPE_LEN {
+            for k in 1..rope_len {
                 rope[k] = move_knot(rope[k], rope[k - 1]);
             }
 
-            visited.insert(rope[ROPE_LEN - 1]);
+            for (cells, &k) in visited.iter_mut().zip(tracked) {
+                cells.insert(rope[k]);
+            }
 
-            // print_state(&rope);
+            on_step(&rope, instr, step + 1 == step_count);
         }
     }
 
-    println!("{}", visited.len());
+    visited
 }
 
 fn move_knot(tail: Loc, head: Loc) -> Loc {
@@ -54,62 +129,85 @@ fn move_knot(tail: Loc, head: Loc) -> Loc {
         return tail;
     }
 
-    let di = head.i - tail.i;
-    let dj = head.j - tail.j;
-
-    if di == 0 {
-        assert_eq!(dj.abs(), 2);
-        return Loc { j: tail.j + dj / 2, ..tail };
-    }
-
-    if dj == 0 {
-        assert_eq!(di.abs(), 2);
-        return Loc { i: tail.i + di / 2, ..tail };
-    }
-
-    let diag = Loc { i: tail.i + 1, j: tail.j + 1 };
-    if diag.is_adjacent(head) {
-        return diag;
-    }
-
-    let diag = Loc { i: tail.i + 1, j: tail.j - 1 };
-    if diag.is_adjacent(head) {
-        return diag;
-    }
-
-    let diag = Loc { i: tail.i - 1, j: tail.j + 1 };
-    if diag.is_adjacent(head) {
-        return diag;
+    Loc {
+        i: tail.i + (head.i - tail.i).signum(),
+        j: tail.j + (head.j - tail.j).signum(),
     }
+}
 
-    let diag = Loc { i: tail.i - 1, j: tail.j - 1 };
-    if diag.is_adjacent(head) {
-        return diag;
+/// Inclusive `(i_min, i_max, j_min, j_max)` box around the start and every
+/// position any knot has ever been at.
+fn bounds(history: &[(Vec<Loc>, usize, bool)]) -> (i32, i32, i32, i32) {
+    let mut ret = (0, 0, 0, 0);
+    for loc in history.iter().flat_map(|(rope, _, _)| rope) {
+        ret.0 = ret.0.min(loc.i);
+        ret.1 = ret.1.max(loc.i);
+        ret.2 = ret.2.min(loc.j);
+        ret.3 = ret.3.max(loc.j);
     }
-
-    panic!("Failed to move knot!");
+    ret
 }
 
-fn print_state(rope: &[Loc]) {
-    for i in (-5..=15).into_iter().rev() {
-        for j in -11..=14 {
+fn render(
+    rope: &[Loc],
+    trail: &HashSet<Loc>,
+    (i_min, i_max, j_min, j_max): (i32, i32, i32, i32),
+) -> String {
+    let mut ret = String::new();
+    for i in (i_min..=i_max).rev() {
+        for j in j_min..=j_max {
             let loc = Loc { i, j };
 
             if let Some(k) = rope.iter().position(|&el| el == loc) {
                 if k == 0 {
-                    eprint!("H");
+                    ret.push('H');
                 } else {
-                    eprint!("{}", k);
+                    ret.push(std::char::from_digit(k as u32, 36).unwrap_or('*'));
                 }
             } else if i == 0 && j == 0 {
-                eprint!("s");
+                ret.push('s');
+            } else if trail.contains(&loc) {
+                ret.push('#');
             } else {
-                eprint!(".");
+                ret.push('.');
             }
         }
-        eprintln!();
+        ret.push('\n');
+    }
+    ret
+}
+
+/// Writes numbered text frames into `dir`, all sharing one bounding box.
+/// The `#` trail is the path of the last knot. Returns the number of frames.
+fn write_frames(
+    dir: &Path,
+    moves: &[(char, usize)],
+    history: &[(Vec<Loc>, usize, bool)],
+    frame_rate: FrameRate,
+) -> Result<usize> {
+    std::fs::create_dir_all(dir)?;
+
+    let bbox = bounds(history);
+    let mut trail = HashSet::from([Loc::default()]);
+    let mut count = 0;
+
+    for (rope, instr, last) in history {
+        trail.insert(*rope.last().unwrap());
+
+        if frame_rate == FrameRate::PerInstr && !last {
+            continue;
+        }
+
+        let (direction, step_count) = moves[*instr];
+        let frame = render(rope, &trail, bbox);
+        std::fs::write(
+            dir.join(format!("frame_{count:06}.txt")),
+            format!("== {direction} {step_count} ==\n\n{frame}"),
+        )?;
+        count += 1;
     }
-    eprintln!();
+
+    Ok(count)
 }
 
 fn gets() -> Option<String> {
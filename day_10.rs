@@ -1,72 +1,709 @@
-use std::io::*;
+use std::{
+    collections::{HashMap, HashSet},
+    fmt::Display,
+    io::*,
+};
+
+const REGISTERS: &[&str] = &["x", "y", "z", "w"];
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+enum Op {
+    Noop,
+    Addx,
+    Add,
+    Set,
+    Jmp,
+    Jnz,
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+enum Kind {
+    Imm,
+    Reg,
+    /// Either a register or an immediate.
+    Value,
+    Label,
+}
 
 #[derive(Debug, Copy, Clone, Eq, PartialEq)]
-enum Instr {
-    Add(i64),
-    Nop,
+enum Operand {
+    Imm(i64),
+    Reg(usize),
+    Target(usize),
+}
+
+struct OpSpec {
+    op: Op,
+    mnemonic: &'static str,
+    operands: &'static [Kind],
+    latency: u64,
+    extended: bool,
+}
+
+/// Everything the assembler knows about. New instructions need a row here
+/// and an arm in `Cpu::complete`.
+const OP_TABLE: &[OpSpec] = &[
+    OpSpec {
+        op: Op::Noop,
+        mnemonic: "noop",
+        operands: &[],
+        latency: 1,
+        extended: false,
+    },
+    OpSpec {
+        op: Op::Addx,
+        mnemonic: "addx",
+        operands: &[Kind::Imm],
+        latency: 2,
+        extended: false,
+    },
+    OpSpec {
+        op: Op::Add,
+        mnemonic: "add",
+        operands: &[Kind::Reg, Kind::Value],
+        latency: 2,
+        extended: true,
+    },
+    OpSpec {
+        op: Op::Set,
+        mnemonic: "set",
+        operands: &[Kind::Reg, Kind::Value],
+        latency: 1,
+        extended: true,
+    },
+    OpSpec {
+        op: Op::Jmp,
+        mnemonic: "jmp",
+        operands: &[Kind::Label],
+        latency: 1,
+        extended: true,
+    },
+    OpSpec {
+        op: Op::Jnz,
+        mnemonic: "jnz",
+        operands: &[Kind::Reg, Kind::Label],
+        latency: 1,
+        extended: true,
+    },
+];
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+struct Instr {
+    op: Op,
+    args: Vec<Operand>,
 }
 
 impl Instr {
-    fn parse(s: &str) -> Instr {
-        if s == "noop" {
-            return Instr::Nop;
-        }
+    fn spec(&self) -> &'static OpSpec {
+        OP_TABLE.iter().find(|spec| spec.op == self.op).unwrap()
+    }
 
-        if let Some(s) = s.strip_prefix("addx ") {
-            return Instr::Add(s.parse().unwrap());
+    fn latency(&self, latencies: &Latencies) -> u64 {
+        latencies.0[OP_TABLE.iter().position(|spec| spec.op == self.op).unwrap()]
+    }
+}
+
+impl Display for Instr {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.spec().mnemonic)?;
+        for arg in &self.args {
+            match arg {
+                Operand::Imm(v) => write!(f, " {v}")?,
+                Operand::Reg(r) => write!(f, " {}", REGISTERS[*r])?,
+                Operand::Target(pc) => write!(f, " L{pc}")?,
+            }
         }
+        Ok(())
+    }
+}
 
-        panic!("Can't parse {s:?} as Instr!");
+#[derive(Debug, Clone, Eq, PartialEq)]
+struct AsmError {
+    line: usize,
+    message: String,
+}
+
+impl Display for AsmError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
     }
 }
 
-fn main() {
-    let program: Vec<Instr> = stdin().lines().map(|rs| Instr::parse(&rs.unwrap())).collect();
+/// Assembles one instruction per line. `;` starts a comment and `name:`
+/// defines a label, which may share its line with an instruction.
+/// Registers and jumps are only available in `extended` mode.
+fn assemble(src: &str, extended: bool) -> std::result::Result<Vec<Instr>, AsmError> {
+    let mut labels = HashMap::new();
+    let mut lines = Vec::new();
+
+    for (k, line) in src.lines().enumerate() {
+        let mut code = line.split(';').next().unwrap().trim();
+
+        if let Some((label, rest)) = code.split_once(':') {
+            let label = label.trim();
+            if label.is_empty() || !label.chars().all(|c| c.is_alphanumeric() || c == '_') {
+                return Err(AsmError {
+                    line: k + 1,
+                    message: format!("bad label {label:?}"),
+                });
+            }
+            if labels.insert(label, lines.len()).is_some() {
+                return Err(AsmError {
+                    line: k + 1,
+                    message: format!("label {label:?} defined twice"),
+                });
+            }
+            code = rest.trim();
+        }
+
+        if !code.is_empty() {
+            lines.push((k + 1, code));
+        }
+    }
+
+    lines
+        .into_iter()
+        .map(|(line, code)| {
+            let error = |message: String| AsmError { line, message };
 
-    let mut tick = 0_u64;
-    let mut register = 1_i64;
+            let mut words = code.split_whitespace();
+            let mnemonic = words.next().unwrap();
+            let spec = OP_TABLE
+                .iter()
+                .find(|spec| spec.mnemonic == mnemonic && (extended || !spec.extended))
+                .ok_or_else(|| error(format!("unknown instruction {mnemonic:?}")))?;
 
-    const INTERESTING_TICKS: &[u64] = &[20, 60, 100, 140, 180, 220];
-    let mut ans = 0_i64;
+            let words: Vec<&str> = words.collect();
+            if words.len() != spec.operands.len() {
+                return Err(error(format!(
+                    "{mnemonic} takes {} operand(s), got {}",
+                    spec.operands.len(),
+                    words.len()
+                )));
+            }
 
-    let mut x = 0_i64;
+            let args = spec
+                .operands
+                .iter()
+                .zip(words)
+                .map(|(kind, word)| {
+                    let reg = REGISTERS.iter().position(|&name| name == word);
+                    match kind {
+                        Kind::Reg | Kind::Value if reg.is_some() => Ok(Operand::Reg(reg.unwrap())),
+                        Kind::Imm | Kind::Value => word
+                            .parse()
+                            .map(Operand::Imm)
+                            .map_err(|_| error(format!("expected a number, got {word:?}"))),
+                        Kind::Reg => Err(error(format!("expected a register, got {word:?}"))),
+                        Kind::Label => labels
+                            .get(word)
+                            .map(|&pc| Operand::Target(pc))
+                            .ok_or_else(|| error(format!("unknown label {word:?}"))),
+                    }
+                })
+                .collect::<std::result::Result<_, _>>()?;
 
-    let mut on_tick = |tick: u64, reg: i64| {
-        // eprintln!("tick: {tick:3}, reg: {reg:3}");
-        if INTERESTING_TICKS.contains(&tick) {
-            // eprintln!("+ {}", (tick as i64) * reg);
-            ans += (tick as i64) * reg;
+            Ok(Instr { op: spec.op, args })
+        })
+        .collect()
+}
+
+/// Lists the program with the cycles each instruction occupies when run
+/// straight through. Jump targets get `L<pc>:` labels.
+fn disassemble(program: &[Instr], latencies: &Latencies) -> String {
+    let targets: HashSet<usize> = program
+        .iter()
+        .flat_map(|instr| &instr.args)
+        .filter_map(|arg| match arg {
+            Operand::Target(pc) => Some(*pc),
+            _ => None,
+        })
+        .collect();
+
+    let mut ret = String::new();
+    let mut cycle = 1;
+    for (pc, instr) in program.iter().enumerate() {
+        if targets.contains(&pc) {
+            ret += &format!("L{pc}:\n");
         }
 
-        if (x - reg).abs() <= 1 {
-            print!("#");
+        let latency = instr.latency(latencies);
+        let cycles = if latency == 1 {
+            format!("{cycle}")
         } else {
-            print!(".");
+            format!("{cycle}-{}", cycle + latency - 1)
+        };
+        ret += &format!("{pc:5}  {cycles:>11}    {instr}\n");
+        cycle += latency;
+    }
+
+    if targets.contains(&program.len()) {
+        ret += &format!("L{}:\n", program.len());
+    }
+
+    ret
+}
+
+/// How many cycles each instruction takes to complete, in `OP_TABLE` order.
+#[derive(Debug, Clone, Eq, PartialEq)]
+struct Latencies(Vec<u64>);
+
+impl Default for Latencies {
+    fn default() -> Self {
+        Latencies(OP_TABLE.iter().map(|spec| spec.latency).collect())
+    }
+}
+
+impl Latencies {
+    fn set(&mut self, mnemonic: &str, cycles: u64) {
+        let k = OP_TABLE
+            .iter()
+            .position(|spec| spec.mnemonic == mnemonic)
+            .unwrap_or_else(|| panic!("Unknown instruction {mnemonic:?}!"));
+        self.0[k] = cycles;
+    }
+}
+
+/// What the CPU looks like *during* a cycle, i.e. before the instruction
+/// that finishes in this cycle has written its result.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+struct Tick {
+    cycle: u64,
+    pc: usize,
+    x: i64,
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+enum Breakpoint {
+    Cycle(u64),
+    RegisterX(i64),
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+enum Stop {
+    Halted,
+    Break(Breakpoint),
+    /// `Cpu::max_cycles` have passed. Jumps make endless loops possible.
+    CycleLimit,
+}
+
+trait Observer {
+    fn during_cycle(&mut self, tick: Tick);
+}
+
+impl<F: FnMut(Tick)> Observer for F {
+    fn during_cycle(&mut self, tick: Tick) {
+        self(tick)
+    }
+}
+
+struct Cpu {
+    program: Vec<Instr>,
+    latencies: Latencies,
+    breakpoints: Vec<Breakpoint>,
+    max_cycles: u64,
+    pc: usize,
+    regs: [i64; REGISTERS.len()],
+    cycle: u64,
+    busy: u64,
+    last_x: Option<i64>,
+    resuming: bool,
+}
+
+impl Cpu {
+    const DEFAULT_MAX_CYCLES: u64 = 100_000;
+
+    fn new(program: Vec<Instr>, latencies: Latencies) -> Cpu {
+        assert!(
+            latencies.0.iter().all(|&l| l > 0),
+            "Latencies must be positive"
+        );
+        Cpu {
+            program,
+            latencies,
+            breakpoints: Vec::new(),
+            max_cycles: Self::DEFAULT_MAX_CYCLES,
+            pc: 0,
+            regs: [1, 0, 0, 0],
+            cycle: 0,
+            busy: 0,
+            last_x: None,
+            resuming: false,
+        }
+    }
+
+    fn x(&self) -> i64 {
+        self.regs[0]
+    }
+
+    fn is_halted(&self) -> bool {
+        self.pc >= self.program.len()
+    }
+
+    /// A breakpoint fires right before the matching cycle starts, so the
+    /// caller can inspect the CPU. The next `step` then executes that cycle.
+    /// Register breakpoints only fire when X has just changed to the value.
+    fn hit_breakpoint(&self) -> Option<Breakpoint> {
+        let next_cycle = self.cycle + 1;
+        self.breakpoints.iter().copied().find(|&bp| match bp {
+            Breakpoint::Cycle(c) => c == next_cycle,
+            Breakpoint::RegisterX(v) => v == self.x() && self.last_x != Some(v),
+        })
+    }
+
+    /// Executes a single cycle, unless the CPU is halted, out of cycles or a
+    /// breakpoint is hit.
+    fn step(&mut self, observers: &mut [&mut dyn Observer]) -> Option<Stop> {
+        if self.is_halted() {
+            return Some(Stop::Halted);
+        }
+        if self.cycle >= self.max_cycles {
+            return Some(Stop::CycleLimit);
+        }
+
+        if !self.resuming {
+            if let Some(bp) = self.hit_breakpoint() {
+                self.resuming = true;
+                return Some(Stop::Break(bp));
+            }
+        }
+        self.resuming = false;
+
+        self.cycle += 1;
+        let tick = Tick {
+            cycle: self.cycle,
+            pc: self.pc,
+            x: self.x(),
+        };
+        for observer in observers.iter_mut() {
+            observer.during_cycle(tick);
         }
 
-        x += 1;
-        if x == 40 {
-            x = 0;
-            println!();
+        self.last_x = Some(self.x());
+
+        self.busy += 1;
+        if self.busy == self.program[self.pc].latency(&self.latencies) {
+            self.complete();
+            self.busy = 0;
+        }
+
+        None
+    }
+
+    /// Applies the effect of the current instruction and moves `pc` on.
+    fn complete(&mut self) {
+        let instr = &self.program[self.pc];
+        let value = |arg: Operand| match arg {
+            Operand::Imm(v) => v,
+            Operand::Reg(r) => self.regs[r],
+            Operand::Target(_) => unreachable!(),
+        };
+        let target = |arg: Operand| match arg {
+            Operand::Target(pc) => pc,
+            _ => unreachable!(),
+        };
+        let reg = |arg: Operand| match arg {
+            Operand::Reg(r) => r,
+            _ => unreachable!(),
+        };
+
+        let mut next_pc = self.pc + 1;
+        match instr.op {
+            Op::Noop => {}
+            Op::Addx => self.regs[0] += value(instr.args[0]),
+            Op::Add => self.regs[reg(instr.args[0])] += value(instr.args[1]),
+            Op::Set => self.regs[reg(instr.args[0])] = value(instr.args[1]),
+            Op::Jmp => next_pc = target(instr.args[0]),
+            Op::Jnz => {
+                if self.regs[reg(instr.args[0])] != 0 {
+                    next_pc = target(instr.args[1]);
+                }
+            }
+        }
+        self.pc = next_pc;
+    }
+
+    fn run(&mut self, observers: &mut [&mut dyn Observer]) -> Stop {
+        loop {
+            if let Some(stop) = self.step(observers) {
+                return stop;
+            }
+        }
+    }
+}
+
+struct SignalStrength {
+    interesting: Vec<u64>,
+    sum: i64,
+}
+
+impl Observer for SignalStrength {
+    fn during_cycle(&mut self, tick: Tick) {
+        if self.interesting.contains(&tick.cycle) {
+            // eprintln!("+ {}", (tick.cycle as i64) * tick.x);
+            self.sum += (tick.cycle as i64) * tick.x;
+        }
+    }
+}
+
+struct Crt {
+    width: usize,
+    pixels: Vec<bool>,
+}
+
+impl Crt {
+    fn new(width: usize) -> Crt {
+        Crt {
+            width,
+            pixels: Vec::new(),
+        }
+    }
+
+    fn rows(&self) -> impl Iterator<Item = &[bool]> {
+        self.pixels.chunks(self.width)
+    }
+}
+
+impl Observer for Crt {
+    fn during_cycle(&mut self, tick: Tick) {
+        let column = (self.pixels.len() % self.width) as i64;
+        self.pixels.push((column - tick.x).abs() <= 1);
+    }
+}
+
+struct Tracer<W: Write> {
+    out: W,
+}
+
+impl<W: Write> Observer for Tracer<W> {
+    fn during_cycle(&mut self, tick: Tick) {
+        writeln!(
+            self.out,
+            "cycle: {:4}, pc: {:4}, x: {:4}",
+            tick.cycle, tick.pc, tick.x
+        )
+        .unwrap();
+    }
+}
+
+fn main() {
+    // Usage: day_10 [--extended] [--disasm] [--trace] [--break-cycle N]... [--break-x V]...
+    //               [--latency MNEMONIC=N]... [--max-cycles N] <input
+    let mut extended = false;
+    let mut disasm = false;
+    let mut trace = false;
+    let mut breakpoints = Vec::new();
+    let mut latencies = Latencies::default();
+    let mut max_cycles = Cpu::DEFAULT_MAX_CYCLES;
+
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().unwrap_or_else(|| panic!("{arg} needs a value"));
+        match arg.as_str() {
+            "--extended" => extended = true,
+            "--disasm" => disasm = true,
+            "--trace" => trace = true,
+            "--break-cycle" => breakpoints.push(Breakpoint::Cycle(value().parse().unwrap())),
+            "--break-x" => breakpoints.push(Breakpoint::RegisterX(value().parse().unwrap())),
+            "--latency" => {
+                let value = value();
+                let (name, cycles) = value.split_once('=').expect("Expected NAME=CYCLES");
+                latencies.set(name, cycles.parse().unwrap());
+            }
+            "--max-cycles" => max_cycles = value().parse().unwrap(),
+            _ => panic!("Unexpected argument {arg:?}!"),
         }
+    }
+
+    let mut src = String::new();
+    stdin().read_to_string(&mut src).unwrap();
+    let program = assemble(&src, extended).unwrap_or_else(|e| panic!("Can't assemble: {e}"));
+
+    if disasm {
+        print!("{}", disassemble(&program, &latencies));
+        return;
+    }
+
+    let mut cpu = Cpu::new(program, latencies);
+    cpu.breakpoints = breakpoints;
+    cpu.max_cycles = max_cycles;
+
+    let mut signal = SignalStrength {
+        interesting: vec![20, 60, 100, 140, 180, 220],
+        sum: 0,
     };
+    let mut crt = Crt::new(40);
+    let mut tracer = Tracer { out: stderr() };
 
+    loop {
+        let mut observers: Vec<&mut dyn Observer> = vec![&mut signal, &mut crt];
+        if trace {
+            observers.push(&mut tracer);
+        }
 
-    for instr in program {
-        tick += 1;
-        on_tick(tick, register);
+        match cpu.run(&mut observers) {
+            Stop::Halted => break,
+            Stop::CycleLimit => {
+                eprintln!(
+                    "Gave up after {max_cycles} cycles: pc = {}, x = {}",
+                    cpu.pc,
+                    cpu.x()
+                );
+                break;
+            }
+            Stop::Break(bp) => {
+                eprintln!(
+                    "Hit {bp:?} before cycle {}: pc = {}, x = {}",
+                    cpu.cycle + 1,
+                    cpu.pc,
+                    cpu.x()
+                );
+            }
+        }
+    }
 
-        if let Instr::Add(v) = instr {
-            tick += 1;
-            on_tick(tick, register);
-            register += v;
+    for row in crt.rows() {
+        let line: String = row.iter().map(|&lit| if lit { '#' } else { '.' }).collect();
+        println!("{line}");
+    }
+
+    if crt.pixels.len() == crt.width * 6 {
+        match ocr::recognize(&crt.pixels, crt.width) {
+            Ok(letters) => println!("{letters}"),
+            Err(e) => eprintln!("{e}"),
         }
     }
 
-    println!("{ans}");
+    println!("{}", signal.sum);
 }
 
+/// Reads the capital letters the CRT draws in the Advent of Code font:
+/// 6 rows high, 4 columns wide plus a blank spacer column.
+mod ocr {
+    use std::fmt;
+
+    const GLYPH_HEIGHT: usize = 6;
+    const GLYPH_WIDTH: usize = 5;
+
+    const FONT: &[(char, [&str; GLYPH_HEIGHT])] = &[
+        ('A', [".##.", "#..#", "#..#", "####", "#..#", "#..#"]),
+        ('B', ["###.", "#..#", "###.", "#..#", "#..#", "###."]),
+        ('C', [".##.", "#..#", "#...", "#...", "#..#", ".##."]),
+        ('E', ["####", "#...", "###.", "#...", "#...", "####"]),
+        ('F', ["####", "#...", "###.", "#...", "#...", "#..."]),
+        ('G', [".##.", "#..#", "#...", "#.##", "#..#", ".###"]),
+        ('H', ["#..#", "#..#", "####", "#..#", "#..#", "#..#"]),
+        ('I', [".###", "..#.", "..#.", "..#.", "..#.", ".###"]),
+        ('J', ["..##", "...#", "...#", "...#", "#..#", ".##."]),
+        ('K', ["#..#", "#.#.", "##..", "#.#.", "#.#.", "#..#"]),
+        ('L', ["#...", "#...", "#...", "#...", "#...", "####"]),
+        ('O', [".##.", "#..#", "#..#", "#..#", "#..#", ".##."]),
+        ('P', ["###.", "#..#", "#..#", "###.", "#...", "#..."]),
+        ('R', ["###.", "#..#", "#..#", "###.", "#.#.", "#..#"]),
+        ('S', [".###", "#...", "#...", ".##.", "...#", "###."]),
+        ('U', ["#..#", "#..#", "#..#", "#..#", "#..#", ".##."]),
+        ('Y', ["#...#", "#...#", ".#.#.", "..#..", "..#..", "..#.."]),
+        ('Z', ["####", "...#", "..#.", ".#..", "#...", "####"]),
+    ];
+
+    #[derive(Debug, Clone, Eq, PartialEq)]
+    pub struct OcrError {
+        /// What could be read, with `?` in place of every unknown glyph.
+        pub partial: String,
+        /// Each unknown glyph's index and its `#`/`.` drawing.
+        pub unknown: Vec<(usize, Vec<String>)>,
+    }
+
+    impl fmt::Display for OcrError {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            writeln!(f, "Couldn't read all letters: {:?}", self.partial)?;
+            for (index, rows) in &self.unknown {
+                writeln!(f, "Unknown glyph #{index}:")?;
+                for row in rows {
+                    writeln!(f, "  {row}")?;
+                }
+            }
+            Ok(())
+        }
+    }
+
+    /// Splits a row-major framebuffer `width` pixels wide into glyphs and reads them.
+    pub fn recognize(pixels: &[bool], width: usize) -> Result<String, OcrError> {
+        assert_eq!(
+            pixels.len(),
+            width * GLYPH_HEIGHT,
+            "Expected exactly {GLYPH_HEIGHT} rows"
+        );
+
+        let mut partial = String::new();
+        let mut unknown = Vec::new();
+
+        for (index, left) in (0..width).step_by(GLYPH_WIDTH).enumerate() {
+            let rows: Vec<String> = (0..GLYPH_HEIGHT)
+                .map(|i| {
+                    (left..(left + GLYPH_WIDTH).min(width))
+                        .map(|j| if pixels[i * width + j] { '#' } else { '.' })
+                        .collect()
+                })
+                .collect();
+
+            match FONT.iter().find(|(_, glyph)| matches(glyph, &rows)) {
+                Some(&(letter, _)) => partial.push(letter),
+                None => {
+                    partial.push('?');
+                    unknown.push((index, rows));
+                }
+            }
+        }
+
+        if unknown.is_empty() {
+            Ok(partial)
+        } else {
+            Err(OcrError { partial, unknown })
+        }
+    }
 
+    /// Glyphs narrower than the cell must have blank pixels in the rest of it.
+    fn matches(glyph: &[&str; GLYPH_HEIGHT], rows: &[String]) -> bool {
+        glyph.iter().zip(rows).all(|(expected, actual)| {
+            actual.len() >= expected.len()
+                && actual.starts_with(expected)
+                && actual[expected.len()..].bytes().all(|c| c == b'.')
+        })
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        fn pixels(rows: &[&str]) -> Vec<bool> {
+            rows.iter()
+                .flat_map(|row| row.bytes().map(|c| c == b'#'))
+                .collect()
+        }
+
+        #[test]
+        fn reads_a_crt_frame() {
+            let frame = pixels(&[
+                "###..####.#..#.####..##....##..##..###..",
+                "#..#....#.#..#.#....#..#....#.#..#.#..#.",
+                "#..#...#..####.###..#.......#.#....###..",
+                "###...#...#..#.#....#.##....#.#....#..#.",
+                "#.#..#....#..#.#....#..#.#..#.#..#.#..#.",
+                "#..#.####.#..#.#.....###..##...##..###..",
+            ]);
+            assert_eq!(recognize(&frame, 40), Ok("RZHFGJCB".to_string()));
+        }
+
+        #[test]
+        fn reports_unknown_glyphs() {
+            let frame = pixels(&["####.", "#..#.", "#..#.", "#..#.", "#..#.", "####."]);
+            let err = recognize(&frame, 5).unwrap_err();
+            assert_eq!(err.partial, "?");
+            assert_eq!(err.unknown.len(), 1);
+        }
+    }
+}
 
 fn gets() -> Option<String> {
     let mut line = String::new();
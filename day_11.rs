@@ -1,104 +1,585 @@
+use std::{
+    collections::HashMap,
+    fmt::{Debug, Display},
+    io::*,
+};
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+enum Operation {
+    Add(u64),
+    Mul(u64),
+    Square,
+}
+
+impl Operation {
+    fn parse(s: &str) -> Operation {
+        let expr = s
+            .strip_prefix("new = old ")
+            .unwrap_or_else(|| panic!("Can't parse {s:?} as Operation!"));
+        match expr.split_once(' ') {
+            Some(("*", "old")) => Operation::Square,
+            Some(("*", value)) => Operation::Mul(value.parse().unwrap()),
+            Some(("+", value)) => Operation::Add(value.parse().unwrap()),
+            _ => panic!("Can't parse {s:?} as Operation!"),
+        }
+    }
+
+    fn apply(self, x: u64) -> u64 {
+        match self {
+            Operation::Add(value) => x.checked_add(value),
+            Operation::Mul(value) => x.checked_mul(value),
+            Operation::Square => x.checked_mul(x),
+        }
+        .unwrap_or_else(|| panic!("Worry level {x} overflows with {self:?}!"))
+    }
+
+    /// `apply` modulo `m`. Goes through u128, so any `m` up to `u64::MAX` works.
+    fn apply_mod(self, x: u64, m: u64) -> u64 {
+        let (x, m) = (x as u128 % m as u128, m as u128);
+        let ret = match self {
+            Operation::Add(value) => x + value as u128,
+            Operation::Mul(value) => x * value as u128,
+            Operation::Square => x * x,
+        };
+        (ret % m) as u64
+    }
+}
+
 #[derive(Debug, Clone)]
 struct Monkey {
     items: Vec<u64>,
-    op: fn(u64) -> u64,
-    test: fn(u64) -> bool,
+    op: Operation,
+    divisor: u64,
+    /// Where to throw when the test fails and when it passes.
     decision: [usize; 2],
-    processed: usize,
 }
 
-fn main() {
-    const MOD: u64 = 2 * 3 * 5 * 7 * 11 * 13 * 17 * 19;
+impl Monkey {
+    /// Parses one "Monkey N:" block of the puzzle input.
+    fn parse(block: &str) -> Monkey {
+        let mut lines = block.lines().map(str::trim).filter(|line| !line.is_empty());
+        let mut field = |name: &str| {
+            let line = lines
+                .next()
+                .unwrap_or_else(|| panic!("Missing {name:?} in {block:?}"));
+            line.strip_prefix(name)
+                .unwrap_or_else(|| panic!("Expected {name:?}, got {line:?}"))
+                .trim()
+                .to_string()
+        };
+
+        field("Monkey");
+
+        let items = field("Starting items:");
+        let items = items
+            .split(',')
+            .map(str::trim)
+            .filter(|s| !s.is_empty())
+            .map(|s| s.parse().unwrap())
+            .collect();
+
+        let op = Operation::parse(&field("Operation:"));
+        let divisor = field("Test: divisible by").parse().unwrap();
+        let if_true = field("If true: throw to monkey").parse().unwrap();
+        let if_false = field("If false: throw to monkey").parse().unwrap();
 
-    let mut monkeys = vec![
-        Monkey {
-            items: vec![52, 60, 85, 69, 75, 75],
-            op: |x| (x * 17) % MOD,
-            test: |x| x % 13 == 0,
-            decision: [7, 6],
-            processed: 0,
-        },
-        Monkey {
-            items: vec![96, 82, 61, 99, 82, 84, 85],
-            op: |x| (x + 8) % MOD,
-            test: |x| x % 7 == 0,
-            decision: [7, 0],
-            processed: 0,
-        },
-        Monkey {
-            items: vec![95, 79],
-            op: |x| (x + 6) % MOD,
-            test: |x| x % 19 == 0,
-            decision: [3, 5],
-            processed: 0,
-        },
-        Monkey {
-            items: vec![88, 50, 82, 65, 77],
-            op: |x| (x * 19) % MOD,
-            test: |x| x % 2 == 0,
-            decision: [1, 4],
-            processed: 0,
-        },
-        Monkey {
-            items: vec![66, 90, 59, 90, 87, 63, 53, 88],
-            op: |x| (x + 7) % MOD,
-            test: |x| x % 5 == 0,
-            decision: [0, 1],
-            processed: 0,
-        },
-        Monkey {
-            items: vec![92, 75, 62],
-            op: |x| (x * x) % MOD,
-            test: |x| x % 3 == 0,
-            decision: [4, 3],
-            processed: 0,
-        },
-        Monkey {
-            items: vec![94, 86, 76, 67],
-            op: |x| (x + 1) % MOD,
-            test: |x| x % 11 == 0,
-            decision: [2, 5],
-            processed: 0,
-        },
         Monkey {
-            items: vec![57],
-            op: |x| (x + 2) % MOD,
-            test: |x| x % 17 == 0,
-            decision: [2, 6],
-            processed: 0,
-        },
-    ];
+            items,
+            op,
+            divisor,
+            decision: [if_false, if_true],
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+enum Relief {
+    /// Worry drops to `worry / k` after each inspection. Squaring can still
+    /// outgrow any fixed width, so worry levels aren't bounded either.
+    DivideBy(u64),
+    /// No relief, but worry is kept modulo the LCM of all divisors.
+    ModuloLcm,
+    /// No relief at all. Worry levels grow without bound.
+    None,
+}
+
+impl Relief {
+    fn parse(s: &str) -> Relief {
+        match s {
+            "lcm" => Relief::ModuloLcm,
+            "none" => Relief::None,
+            _ => match s.strip_prefix("div:") {
+                Some(k) => match k.parse().unwrap() {
+                    0 => panic!("Can't divide worry levels by zero!"),
+                    k => Relief::DivideBy(k),
+                },
+                None => panic!("Can't parse {s:?} as Relief!"),
+            },
+        }
+    }
+}
+
+trait Worry: Clone + Debug + Display {
+    fn from_u64(x: u64) -> Self;
+    fn apply(&self, op: Operation) -> Self;
+    fn div(&self, k: u64) -> Self;
+    fn rem(&self, m: u64) -> u64;
+}
+
+impl Worry for u64 {
+    fn from_u64(x: u64) -> Self {
+        x
+    }
+
+    fn apply(&self, op: Operation) -> Self {
+        op.apply(*self)
+    }
 
-    let n = monkeys.len();
-    for _round in 1..=10000 /* 20 */ {
+    fn div(&self, k: u64) -> Self {
+        self / k
+    }
+
+    fn rem(&self, m: u64) -> u64 {
+        self % m
+    }
+}
+
+/// Just enough of an arbitrary precision unsigned integer for the relief
+/// policies that don't keep worry levels bounded.
+/// Little-endian base 2^32 limbs, no trailing zero limbs.
+#[derive(Debug, Clone, Eq, PartialEq)]
+struct BigUint(Vec<u32>);
+
+impl BigUint {
+    fn trim(mut self) -> Self {
+        while self.0.last() == Some(&0) {
+            self.0.pop();
+        }
+        self
+    }
+
+    fn add_small(&self, x: u64) -> Self {
+        let mut ret = self.0.clone();
+        let mut carry = x as u128;
+        for limb in ret.iter_mut() {
+            if carry == 0 {
+                break;
+            }
+            carry += *limb as u128;
+            *limb = carry as u32;
+            carry >>= 32;
+        }
+        while carry > 0 {
+            ret.push(carry as u32);
+            carry >>= 32;
+        }
+        BigUint(ret)
+    }
+
+    fn mul(&self, o: &BigUint) -> Self {
+        let mut ret = vec![0_u32; self.0.len() + o.0.len()];
+        for (i, &a) in self.0.iter().enumerate() {
+            let mut carry = 0_u64;
+            for (j, &b) in o.0.iter().enumerate() {
+                let cur = ret[i + j] as u64 + a as u64 * b as u64 + carry;
+                ret[i + j] = cur as u32;
+                carry = cur >> 32;
+            }
+            ret[i + o.0.len()] = carry as u32;
+        }
+        BigUint(ret).trim()
+    }
+
+    /// Returns the quotient and the remainder.
+    fn div_rem(&self, k: u64) -> (Self, u64) {
+        assert_ne!(k, 0);
+        let mut quotient = vec![0_u32; self.0.len()];
+        let mut rem = 0_u128;
+        for (i, &limb) in self.0.iter().enumerate().rev() {
+            let cur = (rem << 32) | limb as u128;
+            quotient[i] = (cur / k as u128) as u32;
+            rem = cur % k as u128;
+        }
+        (BigUint(quotient).trim(), rem as u64)
+    }
+}
+
+impl Display for BigUint {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        const CHUNK: u64 = 1_000_000_000;
+
+        let mut chunks = Vec::new();
+        let mut x = self.clone();
+        while !x.0.is_empty() {
+            let (quotient, rem) = x.div_rem(CHUNK);
+            chunks.push(rem);
+            x = quotient;
+        }
+
+        match chunks.pop() {
+            None => write!(f, "0"),
+            Some(top) => {
+                write!(f, "{top}")?;
+                for chunk in chunks.iter().rev() {
+                    write!(f, "{chunk:09}")?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
 
-        for i in 0..n {
-            let mut monkey = monkeys[i].clone();
+impl Worry for BigUint {
+    fn from_u64(x: u64) -> Self {
+        BigUint(vec![x as u32, (x >> 32) as u32]).trim()
+    }
+
+    fn apply(&self, op: Operation) -> Self {
+        match op {
+            Operation::Add(value) => self.add_small(value),
+            Operation::Mul(value) => self.mul(&BigUint::from_u64(value)),
+            Operation::Square => self.mul(self),
+        }
+    }
+
+    fn div(&self, k: u64) -> Self {
+        self.div_rem(k).0
+    }
+
+    fn rem(&self, m: u64) -> u64 {
+        self.div_rem(m).1
+    }
+}
 
-            for &item in &monkey.items {
+/// State of the game at the end of a round.
+#[derive(Debug, Clone)]
+struct Snapshot<W> {
+    round: usize,
+    /// Worry levels of the items each monkey holds.
+    items: Vec<Vec<W>>,
+    /// Total inspections per monkey so far.
+    inspections: Vec<usize>,
+    /// `throws[i][j]` is how many items monkey `i` threw to `j` this round.
+    throws: Vec<Vec<usize>>,
+}
+
+impl<W: Display> Display for Snapshot<W> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "== After round {} ==", self.round)?;
+        for (i, items) in self.items.iter().enumerate() {
+            let items: Vec<String> = items.iter().map(|w| w.to_string()).collect();
+            writeln!(f, "Monkey {i}: {}", items.join(", "))?;
+        }
+        for (i, count) in self.inspections.iter().enumerate() {
+            writeln!(f, "Monkey {i} inspected items {count} times.")?;
+        }
+        writeln!(f, "Throws this round (row = from, column = to):")?;
+        for row in &self.throws {
+            let row: Vec<String> = row.iter().map(|count| format!("{count:3}")).collect();
+            writeln!(f, "  {}", row.join(" "))?;
+        }
+        Ok(())
+    }
+}
+
+struct KeepAway<W> {
+    monkeys: Vec<Monkey>,
+    relief: Relief,
+    modulus: u64,
+    items: Vec<Vec<W>>,
+    inspections: Vec<usize>,
+    round: usize,
+}
+
+impl<W: Worry> KeepAway<W> {
+    fn new(monkeys: Vec<Monkey>, relief: Relief) -> Self {
+        assert!(
+            monkeys
+                .iter()
+                .all(|m| m.decision.iter().all(|&dest| dest < monkeys.len())),
+            "Monkeys throw to a monkey that doesn't exist"
+        );
+
+        let modulus = monkeys.iter().map(|m| m.divisor).fold(1, lcm);
+        let start_worry = |x: u64| match relief {
+            Relief::ModuloLcm => x % modulus,
+            _ => x,
+        };
+        let items = monkeys
+            .iter()
+            .map(|m| {
+                m.items
+                    .iter()
+                    .map(|&x| W::from_u64(start_worry(x)))
+                    .collect()
+            })
+            .collect();
+        let inspections = vec![0; monkeys.len()];
+
+        KeepAway {
+            monkeys,
+            relief,
+            modulus,
+            items,
+            inspections,
+            round: 0,
+        }
+    }
+
+    fn play_round(&mut self) -> Snapshot<W> {
+        let n = self.monkeys.len();
+        let mut throws = vec![vec![0; n]; n];
+
+        for (i, monkey) in self.monkeys.iter().enumerate() {
+            let items = std::mem::take(&mut self.items[i]);
+            self.inspections[i] += items.len();
+
+            for item in items {
                 // eprintln!("Monkey #{i} inspects an item with a worry level of {item}.");
-                let item = (monkey.op)(item) /* / 3 */;
-                let dest = monkey.decision[(monkey.test)(item) as usize];
+                let item = match self.relief {
+                    Relief::DivideBy(k) => item.apply(monkey.op).div(k),
+                    Relief::ModuloLcm => {
+                        let worry = item.rem(self.modulus);
+                        W::from_u64(monkey.op.apply_mod(worry, self.modulus))
+                    }
+                    Relief::None => item.apply(monkey.op),
+                };
+                let dest = monkey.decision[(item.rem(monkey.divisor) == 0) as usize];
                 // eprintln!("Monkey #{i} throws {item} to #{dest}.");
 
-                monkeys[dest].items.push(item);
+                throws[i][dest] += 1;
+                self.items[dest].push(item);
             }
-            monkey.processed += monkey.items.len();
-            monkey.items = vec![];
+        }
 
-            monkeys[i] = monkey;
+        self.round += 1;
+        Snapshot {
+            round: self.round,
+            items: self.items.clone(),
+            inspections: self.inspections.clone(),
+            throws,
         }
+    }
+
+    fn monkey_business(&self) -> usize {
+        let mut processed = self.inspections.clone();
+        processed.sort();
+        processed.reverse();
+
+        processed.iter().take(2).product()
+    }
+}
 
-        // eprintln!("After round {round}:");
-        // for monkey in &monkeys {
-        //     eprintln!(" - {monkey:?}");
-        // }
+fn play<W: Worry>(
+    monkeys: Vec<Monkey>,
+    relief: Relief,
+    rounds: usize,
+    report_every: Option<usize>,
+) -> usize {
+    let mut game = KeepAway::<W>::new(monkeys, relief);
+    for _ in 0..rounds {
+        let snapshot = game.play_round();
+        if report_every.is_some_and(|k| snapshot.round.is_multiple_of(k)) {
+            println!("{snapshot}");
+        }
     }
+    game.monkey_business()
+}
+
+/// One item's path under `Relief::ModuloLcm`. Since there are finitely many
+/// (holder, worry mod LCM) pairs, the state at the start of a round repeats.
+struct Trajectory {
+    /// `prefix[r][i]` is how many times monkey `i` inspected the item in the first `r` rounds.
+    prefix: Vec<Vec<u64>>,
+    cycle_start: usize,
+    cycle_len: usize,
+}
+
+impl Trajectory {
+    fn trace(monkeys: &[Monkey], modulus: u64, mut holder: usize, worry: u64) -> Trajectory {
+        let mut worry = worry % modulus;
+        let mut seen = HashMap::new();
+        let mut prefix = vec![vec![0; monkeys.len()]];
+
+        loop {
+            let round = prefix.len() - 1;
+            if let Some(&cycle_start) = seen.get(&(holder, worry)) {
+                return Trajectory {
+                    prefix,
+                    cycle_start,
+                    cycle_len: round - cycle_start,
+                };
+            }
+            seen.insert((holder, worry), round);
+
+            // Monkeys take turns in order, so an item thrown forward gets
+            // inspected again within the same round.
+            let mut counts = prefix[round].clone();
+            loop {
+                let monkey = &monkeys[holder];
+                counts[holder] += 1;
+                worry = monkey.op.apply_mod(worry, modulus);
+                let dest = monkey.decision[worry.is_multiple_of(monkey.divisor) as usize];
+
+                let same_round = dest > holder;
+                holder = dest;
+                if !same_round {
+                    break;
+                }
+            }
+            prefix.push(counts);
+        }
+    }
+
+    fn inspections(&self, rounds: u64) -> Vec<u64> {
+        let rounds = rounds as u128;
+        let start = self.cycle_start as u128;
+        if rounds < self.prefix.len() as u128 {
+            return self.prefix[rounds as usize].clone();
+        }
 
-    let mut processed: Vec<_> = monkeys.iter().map(|m| m.processed).collect();
-    processed.sort();
-    processed.reverse();
+        let cycles = (rounds - start) / self.cycle_len as u128;
+        let rest = ((rounds - start) % self.cycle_len as u128) as usize;
 
-    let business = processed[0] * processed[1];
+        let before = &self.prefix[self.cycle_start];
+        let after = &self.prefix[self.cycle_start + self.cycle_len];
+        self.prefix[self.cycle_start + rest]
+            .iter()
+            .zip(before.iter().zip(after))
+            .map(|(&base, (&b, &a))| base + cycles as u64 * (a - b))
+            .collect()
+    }
+}
+
+/// Same answer as `play` with `Relief::ModuloLcm`, but follows every item on
+/// its own until its trajectory cycles instead of simulating all the rounds.
+fn play_with_cycles(monkeys: &[Monkey], rounds: u64) -> u128 {
+    let modulus = monkeys.iter().map(|m| m.divisor).fold(1, lcm);
+    let mut inspections = vec![0_u64; monkeys.len()];
+
+    for (holder, monkey) in monkeys.iter().enumerate() {
+        for &worry in &monkey.items {
+            let trajectory = Trajectory::trace(monkeys, modulus, holder, worry);
+
+            for (total, count) in inspections.iter_mut().zip(trajectory.inspections(rounds)) {
+                *total += count;
+            }
+        }
+    }
+
+    inspections.sort();
+    inspections.reverse();
+    inspections.iter().take(2).map(|&x| x as u128).product()
+}
+
+fn main() {
+    // Usage: day_11 [--rounds N] [--relief div:K|lcm|none] [--report EVERY] [--cycles] <input
+    let mut rounds = 10000;
+    let mut relief = Relief::ModuloLcm;
+    let mut report_every = None;
+    let mut cycles = false;
+
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        if arg == "--cycles" {
+            cycles = true;
+            continue;
+        }
+
+        let value = args.next().unwrap_or_else(|| panic!("{arg} needs a value"));
+        match arg.as_str() {
+            "--rounds" => rounds = value.parse().unwrap(),
+            "--relief" => relief = Relief::parse(&value),
+            "--report" => report_every = Some(value.parse().unwrap()),
+            _ => panic!("Unexpected argument {arg:?}!"),
+        }
+    }
+
+    let mut input = String::new();
+    stdin().read_to_string(&mut input).unwrap();
+
+    let monkeys: Vec<Monkey> = input
+        .split("\n\n")
+        .filter(|block| !block.trim().is_empty())
+        .map(Monkey::parse)
+        .collect();
+
+    if cycles {
+        assert_eq!(
+            relief,
+            Relief::ModuloLcm,
+            "Cycle detection needs the modulo-LCM relief"
+        );
+        assert!(
+            report_every.is_none(),
+            "Cycle detection doesn't produce per-round reports"
+        );
+
+        let business = play_with_cycles(&monkeys, rounds as u64);
+        println!("Monkey business level is {business}.");
+        return;
+    }
+
+    let business = match relief {
+        Relief::ModuloLcm => play::<u64>(monkeys, relief, rounds, report_every),
+        Relief::DivideBy(_) | Relief::None => {
+            play::<BigUint>(monkeys, relief, rounds, report_every)
+        }
+    };
     println!("Monkey business level is {business}.");
 }
+
+fn gcd(a: u64, b: u64) -> u64 {
+    if b == 0 {
+        a
+    } else {
+        gcd(b, a % b)
+    }
+}
+
+fn lcm(a: u64, b: u64) -> u64 {
+    (a / gcd(a, b))
+        .checked_mul(b)
+        .unwrap_or_else(|| panic!("LCM of the divisors doesn't fit in a u64!"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EXAMPLE: &str = "Monkey 0:
+  Starting items: 79, 98
+  Operation: new = old * 19
+  Test: divisible by 23
+    If true: throw to monkey 2
+    If false: throw to monkey 3
+
+Monkey 1:
+  Starting items: 54, 65, 75, 74
+  Operation: new = old + 6
+  Test: divisible by 19
+    If true: throw to monkey 2
+    If false: throw to monkey 0
+
+Monkey 2:
+  Starting items: 79, 60, 97
+  Operation: new = old * old
+  Test: divisible by 13
+    If true: throw to monkey 1
+    If false: throw to monkey 3
+
+Monkey 3:
+  Starting items: 74
+  Operation: new = old + 3
+  Test: divisible by 17
+    If true: throw to monkey 0
+    If false: throw to monkey 1";
+
+    #[test]
+    fn cycles_match_simulation() {
+        let monkeys: Vec<Monkey> = EXAMPLE.split("\n\n").map(Monkey::parse).collect();
+        let simulated = play::<u64>(monkeys.clone(), Relief::ModuloLcm, 10000, None);
+        assert_eq!(simulated, 2713310158);
+        assert_eq!(play_with_cycles(&monkeys, 10000), simulated as u128);
+    }
+}
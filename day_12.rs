@@ -1,8 +1,77 @@
-use std::{io::*, collections::VecDeque};
+use std::{
+    cmp::Reverse,
+    collections::{BinaryHeap, VecDeque},
+    io::*,
+};
 
 type Map = Vec<Vec<u8>>;
+type Pos = (usize, usize);
+type Dist = Vec<Vec<Option<u64>>>;
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+enum Cost {
+    /// Every step costs 1.
+    Uniform,
+    /// A step costs 1 plus this much per level of height difference, up or down.
+    PerLevel(u64),
+}
+
+/// Which moves are legal and what they cost.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+struct Rules {
+    max_climb: u8,
+    max_descent: u8,
+    cost: Cost,
+    diagonal: bool,
+}
+
+impl Default for Rules {
+    /// The puzzle's rules: at most one step up, any step down.
+    fn default() -> Self {
+        Rules {
+            max_climb: 1,
+            max_descent: u8::MAX,
+            cost: Cost::Uniform,
+            diagonal: false,
+        }
+    }
+}
+
+impl Rules {
+    fn step_cost(&self, map: &Map, (fi, fj): Pos, (ti, tj): Pos) -> Option<u64> {
+        let (from, to) = (map[fi][fj], map[ti][tj]);
+        if to > from && to - from > self.max_climb {
+            return None;
+        }
+        if from > to && from - to > self.max_descent {
+            return None;
+        }
+
+        match self.cost {
+            Cost::Uniform => Some(1),
+            Cost::PerLevel(k) => Some(1 + k * from.abs_diff(to) as u64),
+        }
+    }
+}
 
 fn main() {
+    // Usage: day_12 [--path] [--max-climb K] [--max-descent D] [--cost-per-level C] [--diagonal] <input
+    let mut show_path = false;
+    let mut rules = Rules::default();
+
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().unwrap_or_else(|| panic!("{arg} needs a value"));
+        match arg.as_str() {
+            "--path" => show_path = true,
+            "--max-climb" => rules.max_climb = value().parse().unwrap(),
+            "--max-descent" => rules.max_descent = value().parse().unwrap(),
+            "--cost-per-level" => rules.cost = Cost::PerLevel(value().parse().unwrap()),
+            "--diagonal" => rules.diagonal = true,
+            _ => panic!("Unexpected argument {arg:?}!"),
+        }
+    }
+
     let mut map: Map = stdin().lines()
         .map(|rs| rs.unwrap().into_bytes())
         .collect();
@@ -12,46 +81,151 @@ fn main() {
     map[si][sj] = b'a';
     map[ei][ej] = b'z';
 
-    println!("Answer to part one: {}", bfs(&map, (si, sj), (ei, ej)));
+    // A single search from the end answers both parts.
+    let dist = reverse_search(&map, &rules, (ei, ej));
+
+    match dist[si][sj] {
+        Some(d) => println!("Answer to part one: {d}"),
+        None => println!("Answer to part one: E is unreachable from S"),
+    }
 
-    let ans = map_find(&map, b'a')
+    let best_start = map_find(&map, b'a')
         .into_iter()
-        .map(|start| bfs(&map, start, (ei, ej)))
-        .min()
-        .unwrap();
+        .filter_map(|(i, j)| dist[i][j].map(|d| (d, (i, j))))
+        .min();
 
-    println!("Answer to part two: {ans}");
+    match best_start {
+        Some((d, _)) => println!("Answer to part two: {d}"),
+        None => println!("Answer to part two: E is unreachable from any a"),
+    }
+
+    if show_path {
+        for start in [Some((si, sj)), best_start.map(|(_, start)| start)]
+            .into_iter()
+            .flatten()
+        {
+            if let Some(path) = shortest_path(&map, &rules, &dist, start) {
+                eprintln!("{}", render_path(&map, &path));
+            }
+        }
+    }
 }
 
-fn bfs(map: &Map, (si, sj): (usize, usize), (ei, ej): (usize, usize)) -> usize {
-    let n = map.len();
+fn neighbours(map: &Map, rules: &Rules, (i, j): Pos) -> impl Iterator<Item = Pos> {
+    let n = map.len() as isize;
+    let m = map[0].len() as isize;
+    let diagonal = rules.diagonal;
+
+    (-1..=1_isize)
+        .flat_map(|di| (-1..=1_isize).map(move |dj| (di, dj)))
+        .filter(move |&(di, dj)| (di, dj) != (0, 0) && (diagonal || di == 0 || dj == 0))
+        .map(move |(di, dj)| (i as isize + di, j as isize + dj))
+        .filter(move |&(i, j)| 0 <= i && i < n && 0 <= j && j < m)
+        .map(|(i, j)| (i as usize, j as usize))
+}
+
+/// Cost of the cheapest route from every cell to `end`, walking the rules backwards:
+/// we may step from `cur` to `prev` only if `prev -> cur` is a legal move.
+/// Plain BFS is enough while every step costs the same.
+fn reverse_search(map: &Map, rules: &Rules, end: Pos) -> Dist {
     let m = map[0].len();
     assert!(map.iter().all(|row| row.len() == m));
 
-    let mut dist = vec![vec![usize::MAX; m]; n];
-    dist[si][sj] = 0;
+    match rules.cost {
+        Cost::Uniform => reverse_bfs(map, rules, end),
+        Cost::PerLevel(_) => reverse_dijkstra(map, rules, end),
+    }
+}
+
+fn reverse_bfs(map: &Map, rules: &Rules, (ei, ej): Pos) -> Dist {
+    let mut dist = vec![vec![None; map[0].len()]; map.len()];
+    dist[ei][ej] = Some(0);
 
     let mut queue = VecDeque::new();
-    queue.push_back((si, sj));
+    queue.push_back((ei, ej));
 
-    while let Some((ci, cj)) = queue.pop_front() {
-        assert_ne!(dist[ci][cj], usize::MAX);
-        let max_h = map[ci][cj] + 1;
+    while let Some(cur) = queue.pop_front() {
+        let d = dist[cur.0][cur.1].unwrap();
 
-        let mut go = |i: usize, j: usize| {
-            if map[i][j] <= max_h && dist[i][j] == usize::MAX {
-                dist[i][j] = dist[ci][cj] + 1;
+        for (i, j) in neighbours(map, rules, cur) {
+            if dist[i][j].is_none() && rules.step_cost(map, (i, j), cur).is_some() {
+                dist[i][j] = Some(d + 1);
                 queue.push_back((i, j));
             }
+        }
+    }
+
+    dist
+}
+
+fn reverse_dijkstra(map: &Map, rules: &Rules, (ei, ej): Pos) -> Dist {
+    let mut dist: Dist = vec![vec![None; map[0].len()]; map.len()];
+    dist[ei][ej] = Some(0);
+
+    let mut heap = BinaryHeap::new();
+    heap.push(Reverse((0, (ei, ej))));
+
+    while let Some(Reverse((d, cur))) = heap.pop() {
+        if dist[cur.0][cur.1] != Some(d) {
+            continue;
+        }
+
+        for (i, j) in neighbours(map, rules, cur) {
+            if let Some(cost) = rules.step_cost(map, (i, j), cur) {
+                if dist[i][j].is_none_or(|old| d + cost < old) {
+                    dist[i][j] = Some(d + cost);
+                    heap.push(Reverse((d + cost, (i, j))));
+                }
+            }
+        }
+    }
+
+    dist
+}
+
+/// Follows the search tree from `start` down to the end, if it's reachable.
+fn shortest_path(map: &Map, rules: &Rules, dist: &Dist, start: Pos) -> Option<Vec<Pos>> {
+    let mut cur = start;
+    let mut d = dist[cur.0][cur.1]?;
+    let mut path = vec![cur];
+
+    while d > 0 {
+        let (next, cost) = neighbours(map, rules, cur)
+            .filter_map(|(i, j)| Some(((i, j), rules.step_cost(map, cur, (i, j))?)))
+            .find(|&((i, j), cost)| dist[i][j].map(|x| x + cost) == Some(d))
+            .unwrap();
+        cur = next;
+        d -= cost;
+        path.push(cur);
+    }
+
+    Some(path)
+}
+
+fn render_path(map: &Map, path: &[Pos]) -> String {
+    let mut canvas = vec![vec![b'.'; map[0].len()]; map.len()];
+
+    for step in path.windows(2) {
+        let ((fi, fj), (ti, tj)) = (step[0], step[1]);
+        canvas[fi][fj] = match (ti as isize - fi as isize, tj as isize - fj as isize) {
+            (-1, 0) => b'^',
+            (1, 0) => b'v',
+            (0, -1) => b'<',
+            (0, 1) => b'>',
+            (-1, -1) | (1, 1) => b'\\',
+            (-1, 1) | (1, -1) => b'/',
+            _ => unreachable!(),
         };
+    }
 
-        if ci > 0 { go(ci - 1, cj); }
-        if cj > 0 { go(ci, cj - 1); }
-        if ci + 1 < n { go(ci + 1, cj); }
-        if cj + 1 < m { go(ci, cj + 1); }
+    if let Some(&(ei, ej)) = path.last() {
+        canvas[ei][ej] = b'E';
     }
 
-    dist[ei][ej]
+    canvas
+        .into_iter()
+        .map(|row| String::from_utf8(row).unwrap() + "\n")
+        .collect()
 }
 
 fn map_find(map: &Map, needle: u8) -> Vec<(usize, usize)> {
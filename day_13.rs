@@ -15,7 +15,7 @@ impl Display for Pac {
                 let mut first = true;
                 for sub in vec {
                     if !first {
-                        write!(f, ", ")?;
+                        write!(f, ",")?;
                     }
                     write!(f, "{}", sub)?;
                     first = false;
@@ -26,41 +26,165 @@ impl Display for Pac {
     }
 }
 
-impl Pac {
-    fn parse(s: &str) -> Pac {
-        if let Ok(value) = s.parse() {
-            return Pac::Int(value);
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+enum Token {
+    Open,
+    Close,
+    Comma,
+    Int(i32),
+    End,
+}
+
+impl Display for Token {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Token::Open => write!(f, "'['"),
+            Token::Close => write!(f, "']'"),
+            Token::Comma => write!(f, "','"),
+            Token::Int(value) => write!(f, "{value}"),
+            Token::End => write!(f, "end of input"),
         }
+    }
+}
 
-        assert!(s.len() >= 2);
-        assert_eq!(s.chars().next(), Some('['));
-        assert_eq!(s.chars().last(), Some(']'));
+#[derive(Debug, Clone, Eq, PartialEq)]
+struct ParseError {
+    /// Character offset into the input.
+    pos: usize,
+    message: String,
+}
 
-        let mut vec = Vec::new();
+impl Display for ParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "at position {}: {}", self.pos, self.message)
+    }
+}
 
-        let mut balance = 0_usize;
-        let mut prev = 1_usize;
+fn tokenize(s: &str) -> std::result::Result<Vec<(usize, Token)>, ParseError> {
+    let chars: Vec<char> = s.chars().collect();
+    let mut tokens = Vec::new();
+    let mut k = 0;
 
-        for (i, c) in s.chars().enumerate() {
-            match c {
-                '[' => balance += 1,
-                ']' => {
-                    balance -= 1;
-                    if balance == 0 && prev < i {
-                        vec.push(Pac::parse(&s[prev..i]));
-                    }
+    while k < chars.len() {
+        let c = chars[k];
+        let token = match c {
+            _ if c.is_whitespace() => {
+                k += 1;
+                continue;
+            }
+            '[' => Token::Open,
+            ']' => Token::Close,
+            ',' => Token::Comma,
+            '-' | '0'..='9' => {
+                let start = k;
+                k += 1;
+                while k < chars.len() && chars[k].is_ascii_digit() {
+                    k += 1;
                 }
-                ',' => {
-                    if balance == 1 {
-                        vec.push(Pac::parse(&s[prev..i]));
-                        prev = i + 1;
+
+                let literal: String = chars[start..k].iter().collect();
+                let value = literal.parse().map_err(|_| ParseError {
+                    pos: start,
+                    message: format!("bad integer {literal:?}"),
+                })?;
+                tokens.push((start, Token::Int(value)));
+                continue;
+            }
+            _ => {
+                return Err(ParseError {
+                    pos: k,
+                    message: format!("unexpected character {c:?}"),
+                });
+            }
+        };
+
+        tokens.push((k, token));
+        k += 1;
+    }
+
+    tokens.push((chars.len(), Token::End));
+    Ok(tokens)
+}
+
+/// How deeply lists may nest. Comparing, printing and dropping packets all
+/// recurse, so anything deeper is rejected while parsing.
+const MAX_DEPTH: usize = 1000;
+
+/// Recursive descent over the tokens, which always end with `Token::End`.
+struct Parser {
+    tokens: Vec<(usize, Token)>,
+    next: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> (usize, Token) {
+        self.tokens[self.next]
+    }
+
+    fn at_end(&self) -> bool {
+        self.peek().1 == Token::End
+    }
+
+    fn error<T>(&self, message: &str) -> std::result::Result<T, ParseError> {
+        let (pos, found) = self.peek();
+        Err(ParseError {
+            pos,
+            message: format!("{message}, found {found}"),
+        })
+    }
+
+    fn packet(&mut self, depth: usize) -> std::result::Result<Pac, ParseError> {
+        match self.peek().1 {
+            Token::Int(value) => {
+                self.next += 1;
+                Ok(Pac::Int(value))
+            }
+            Token::Open => {
+                if depth == MAX_DEPTH {
+                    return Err(ParseError {
+                        pos: self.peek().0,
+                        message: format!("lists nested deeper than {MAX_DEPTH}"),
+                    });
+                }
+                self.next += 1;
+                let mut vec = Vec::new();
+
+                if self.peek().1 == Token::Close {
+                    self.next += 1;
+                    return Ok(Pac::List(vec));
+                }
+
+                loop {
+                    vec.push(self.packet(depth + 1)?);
+
+                    match self.peek().1 {
+                        Token::Comma => self.next += 1,
+                        Token::Close => {
+                            self.next += 1;
+                            return Ok(Pac::List(vec));
+                        }
+                        _ => return self.error("expected ',' or ']'"),
                     }
                 }
-                _ => {}
             }
+            _ => self.error("expected '[' or an integer"),
         }
+    }
+}
+
+impl Pac {
+    fn parse(s: &str) -> std::result::Result<Pac, ParseError> {
+        let mut parser = Parser {
+            tokens: tokenize(s)?,
+            next: 0,
+        };
+        let pac = parser.packet(0)?;
 
-        Pac::List(vec)
+        if !parser.at_end() {
+            return parser.error("expected end of input");
+        }
+
+        Ok(pac)
     }
 }
 
@@ -86,20 +210,528 @@ impl Ord for Pac {
     }
 }
 
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+enum Side {
+    Left,
+    Right,
+}
+
+impl Display for Side {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Side::Left => write!(f, "left"),
+            Side::Right => write!(f, "right"),
+        }
+    }
+}
+
+/// Why a comparison came out the way it did.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+enum Reason {
+    /// This side had the smaller integer.
+    Smaller(Side),
+    /// This side's list ran out of items first.
+    RanOut(Side),
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+enum PathStep {
+    /// Went into the items at this index on both sides.
+    Index(usize),
+    /// Wrapped this side's integer into a single-item list.
+    Promoted(Side),
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+enum Event {
+    Compare(Pac, Pac),
+    Promote(Side, Pac),
+    Decide(Reason),
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+struct Explanation {
+    ordering: Ordering,
+    /// How to get from the top level to where the decision was made.
+    path: Vec<PathStep>,
+    /// `None` if the packets are equal.
+    reason: Option<Reason>,
+    /// Every step taken, with its nesting depth.
+    trace: Vec<(usize, Event)>,
+}
+
+impl Display for Explanation {
+    /// Renders the trace the way the puzzle statement does.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (depth, event) in &self.trace {
+            write!(f, "{:indent$}- ", "", indent = 2 * depth)?;
+            match event {
+                Event::Compare(a, b) => writeln!(f, "Compare {a} vs {b}")?,
+                Event::Promote(side, pac) => writeln!(
+                    f,
+                    "Mixed types; convert {side} to {pac} and retry comparison"
+                )?,
+                Event::Decide(Reason::Smaller(Side::Left)) => {
+                    writeln!(f, "Left side is smaller, so inputs are in the right order")?
+                }
+                Event::Decide(Reason::Smaller(Side::Right)) => writeln!(
+                    f,
+                    "Right side is smaller, so inputs are not in the right order"
+                )?,
+                Event::Decide(Reason::RanOut(Side::Left)) => writeln!(
+                    f,
+                    "Left side ran out of items, so inputs are in the right order"
+                )?,
+                Event::Decide(Reason::RanOut(Side::Right)) => writeln!(
+                    f,
+                    "Right side ran out of items, so inputs are not in the right order"
+                )?,
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Pac {
+    /// Same result as `cmp`, along with how it was reached.
+    fn compare_explained(&self, other: &Pac) -> Explanation {
+        let mut path = Vec::new();
+        let mut trace = Vec::new();
+        let reason = explain(self, other, 0, &mut path, &mut trace);
+
+        let ordering = match reason {
+            None => Ordering::Equal,
+            Some(Reason::Smaller(Side::Left) | Reason::RanOut(Side::Left)) => Ordering::Less,
+            Some(Reason::Smaller(Side::Right) | Reason::RanOut(Side::Right)) => Ordering::Greater,
+        };
+
+        Explanation {
+            ordering,
+            path,
+            reason,
+            trace,
+        }
+    }
+}
+
+/// Leaves `path` pointing at the decision if there was one.
+fn explain(
+    a: &Pac,
+    b: &Pac,
+    depth: usize,
+    path: &mut Vec<PathStep>,
+    trace: &mut Vec<(usize, Event)>,
+) -> Option<Reason> {
+    use Pac::*;
+    trace.push((depth, Event::Compare(a.clone(), b.clone())));
+
+    let (a_vec, b_vec) = match (a, b) {
+        (Int(x), Int(y)) => {
+            let reason = match x.cmp(y) {
+                Ordering::Less => Reason::Smaller(Side::Left),
+                Ordering::Greater => Reason::Smaller(Side::Right),
+                Ordering::Equal => return None,
+            };
+            trace.push((depth + 1, Event::Decide(reason)));
+            return Some(reason);
+        }
+        (Int(_), List(_)) | (List(_), Int(_)) => {
+            let side = if matches!(a, Int(_)) {
+                Side::Left
+            } else {
+                Side::Right
+            };
+            let (a, b) = match side {
+                Side::Left => (List(vec![a.clone()]), b.clone()),
+                Side::Right => (a.clone(), List(vec![b.clone()])),
+            };
+            let promoted = if side == Side::Left { &a } else { &b };
+            trace.push((depth + 1, Event::Promote(side, promoted.clone())));
+
+            path.push(PathStep::Promoted(side));
+            let reason = explain(&a, &b, depth + 1, path, trace);
+            if reason.is_none() {
+                path.pop();
+            }
+            return reason;
+        }
+        (List(a_vec), List(b_vec)) => (a_vec, b_vec),
+    };
+
+    for (k, (x, y)) in a_vec.iter().zip(b_vec).enumerate() {
+        path.push(PathStep::Index(k));
+        if let Some(reason) = explain(x, y, depth + 1, path, trace) {
+            return Some(reason);
+        }
+        path.pop();
+    }
+
+    let reason = match a_vec.len().cmp(&b_vec.len()) {
+        Ordering::Less => Reason::RanOut(Side::Left),
+        Ordering::Greater => Reason::RanOut(Side::Right),
+        Ordering::Equal => return None,
+    };
+    trace.push((depth + 1, Event::Decide(reason)));
+    Some(reason)
+}
+
+/// A JSON document. Packets are JSON arrays of integers, which makes it an
+/// easy way to trade them with other tools.
+#[derive(Debug, Clone, PartialEq)]
+enum Json {
+    Null,
+    Bool(bool),
+    Number(f64),
+    String(String),
+    Array(Vec<Json>),
+    Object(Vec<(String, Json)>),
+}
+
+impl Display for Json {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Json::Null => write!(f, "null"),
+            Json::Bool(value) => write!(f, "{value}"),
+            Json::Number(value) => write!(f, "{value}"),
+            Json::String(value) => write_json_string(f, value),
+            Json::Array(items) => {
+                write!(f, "[")?;
+                for (k, item) in items.iter().enumerate() {
+                    if k > 0 {
+                        write!(f, ",")?;
+                    }
+                    write!(f, "{item}")?;
+                }
+                write!(f, "]")
+            }
+            Json::Object(fields) => {
+                write!(f, "{{")?;
+                for (k, (key, value)) in fields.iter().enumerate() {
+                    if k > 0 {
+                        write!(f, ",")?;
+                    }
+                    write_json_string(f, key)?;
+                    write!(f, ":{value}")?;
+                }
+                write!(f, "}}")
+            }
+        }
+    }
+}
+
+fn write_json_string(f: &mut std::fmt::Formatter<'_>, s: &str) -> std::fmt::Result {
+    write!(f, "\"")?;
+    for c in s.chars() {
+        match c {
+            '"' => write!(f, "\\\"")?,
+            '\\' => write!(f, "\\\\")?,
+            '\n' => write!(f, "\\n")?,
+            '\r' => write!(f, "\\r")?,
+            '\t' => write!(f, "\\t")?,
+            c if (c as u32) < 0x20 => write!(f, "\\u{:04x}", c as u32)?,
+            c => write!(f, "{c}")?,
+        }
+    }
+    write!(f, "\"")
+}
+
+struct JsonParser {
+    chars: Vec<char>,
+    pos: usize,
+}
+
+impl JsonParser {
+    fn error<T>(&self, message: &str) -> std::result::Result<T, ParseError> {
+        Err(ParseError {
+            pos: self.pos,
+            message: message.to_string(),
+        })
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self.pos < self.chars.len() && self.chars[self.pos].is_whitespace() {
+            self.pos += 1;
+        }
+    }
+
+    fn eat(&mut self, c: char) -> bool {
+        self.skip_whitespace();
+        let found = self.chars.get(self.pos) == Some(&c);
+        if found {
+            self.pos += 1;
+        }
+        found
+    }
+
+    fn keyword(&mut self, word: &str, value: Json) -> std::result::Result<Json, ParseError> {
+        let end = self.pos + word.chars().count();
+        if end <= self.chars.len() && self.chars[self.pos..end].iter().copied().eq(word.chars()) {
+            self.pos = end;
+            Ok(value)
+        } else {
+            self.error("unexpected token")
+        }
+    }
+
+    fn value(&mut self, depth: usize) -> std::result::Result<Json, ParseError> {
+        self.skip_whitespace();
+        if depth == MAX_DEPTH && matches!(self.chars.get(self.pos), Some('[' | '{')) {
+            return self.error(&format!("values nested deeper than {MAX_DEPTH}"));
+        }
+        match self.chars.get(self.pos) {
+            None => self.error("expected a value, found end of input"),
+            Some('n') => self.keyword("null", Json::Null),
+            Some('t') => self.keyword("true", Json::Bool(true)),
+            Some('f') => self.keyword("false", Json::Bool(false)),
+            Some('"') => self.string().map(Json::String),
+            Some('-' | '0'..='9') => self.number(),
+            Some('[') => {
+                self.pos += 1;
+                let mut items = Vec::new();
+                if self.eat(']') {
+                    return Ok(Json::Array(items));
+                }
+                loop {
+                    items.push(self.value(depth + 1)?);
+                    if self.eat(']') {
+                        return Ok(Json::Array(items));
+                    }
+                    if !self.eat(',') {
+                        return self.error("expected ',' or ']'");
+                    }
+                }
+            }
+            Some('{') => {
+                self.pos += 1;
+                let mut fields = Vec::new();
+                if self.eat('}') {
+                    return Ok(Json::Object(fields));
+                }
+                loop {
+                    self.skip_whitespace();
+                    let key = self.string()?;
+                    if !self.eat(':') {
+                        return self.error("expected ':'");
+                    }
+                    fields.push((key, self.value(depth + 1)?));
+                    if self.eat('}') {
+                        return Ok(Json::Object(fields));
+                    }
+                    if !self.eat(',') {
+                        return self.error("expected ',' or '}'");
+                    }
+                }
+            }
+            Some(&c) => self.error(&format!("unexpected character {c:?}")),
+        }
+    }
+
+    fn number(&mut self) -> std::result::Result<Json, ParseError> {
+        let start = self.pos;
+        while self.pos < self.chars.len()
+            && matches!(
+                self.chars[self.pos],
+                '-' | '+' | '.' | 'e' | 'E' | '0'..='9'
+            )
+        {
+            self.pos += 1;
+        }
+
+        let literal: String = self.chars[start..self.pos].iter().collect();
+        match literal.parse() {
+            Ok(value) => Ok(Json::Number(value)),
+            Err(_) => Err(ParseError {
+                pos: start,
+                message: format!("bad number {literal:?}"),
+            }),
+        }
+    }
+
+    fn string(&mut self) -> std::result::Result<String, ParseError> {
+        if self.chars.get(self.pos) != Some(&'"') {
+            return self.error("expected a string");
+        }
+        self.pos += 1;
+
+        let mut ret = String::new();
+        loop {
+            let Some(&c) = self.chars.get(self.pos) else {
+                return self.error("unterminated string");
+            };
+            self.pos += 1;
+
+            match c {
+                '"' => return Ok(ret),
+                '\\' => {
+                    let Some(&escape) = self.chars.get(self.pos) else {
+                        return self.error("unterminated string");
+                    };
+                    self.pos += 1;
+                    ret.push(match escape {
+                        'n' => '\n',
+                        'r' => '\r',
+                        't' => '\t',
+                        'b' => '\u{8}',
+                        'f' => '\u{c}',
+                        'u' => {
+                            let hex: String = self.chars.iter().skip(self.pos).take(4).collect();
+                            self.pos += 4;
+                            match u32::from_str_radix(&hex, 16).ok().and_then(char::from_u32) {
+                                Some(c) => c,
+                                None => return self.error("bad unicode escape"),
+                            }
+                        }
+                        c => c,
+                    });
+                }
+                c => ret.push(c),
+            }
+        }
+    }
+}
+
+impl Json {
+    fn parse(s: &str) -> std::result::Result<Json, ParseError> {
+        let mut parser = JsonParser {
+            chars: s.chars().collect(),
+            pos: 0,
+        };
+        let value = parser.value(0)?;
+
+        parser.skip_whitespace();
+        if parser.pos < parser.chars.len() {
+            return parser.error("expected end of input");
+        }
+
+        Ok(value)
+    }
+}
+
+impl From<&Pac> for Json {
+    fn from(pac: &Pac) -> Json {
+        match pac {
+            Pac::Int(value) => Json::Number(*value as f64),
+            Pac::List(vec) => Json::Array(vec.iter().map(Json::from).collect()),
+        }
+    }
+}
+
+impl TryFrom<&Json> for Pac {
+    type Error = String;
+
+    fn try_from(json: &Json) -> std::result::Result<Pac, String> {
+        match json {
+            Json::Number(value) if value.fract() == 0.0 && value.abs() <= i32::MAX as f64 => {
+                Ok(Pac::Int(*value as i32))
+            }
+            Json::Array(items) => items
+                .iter()
+                .map(Pac::try_from)
+                .collect::<std::result::Result<_, _>>()
+                .map(Pac::List),
+            _ => Err(format!("{json} is neither an array nor a 32-bit integer")),
+        }
+    }
+}
+
+/// `day_13 sort [FILE]`: sorts one JSON packet per line (blank lines are
+/// skipped), together with the dividers, and prints them in order.
+fn sort_packets(path: Option<&str>, dividers: &[Pac]) {
+    let text = match path {
+        Some(path) if path != "-" => {
+            std::fs::read_to_string(path).unwrap_or_else(|e| panic!("Can't read {path}: {e}"))
+        }
+        _ => {
+            let mut text = String::new();
+            stdin().read_to_string(&mut text).unwrap();
+            text
+        }
+    };
+
+    let mut packets = dividers.to_vec();
+    for (k, line) in text.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let json = Json::parse(line).unwrap_or_else(|e| panic!("Line {}: invalid JSON {e}", k + 1));
+        let pac = Pac::try_from(&json).unwrap_or_else(|e| panic!("Line {}: {e}", k + 1));
+        packets.push(pac);
+    }
+
+    packets.sort();
+    for pac in &packets {
+        println!("{}", Json::from(pac));
+    }
+
+    if !dividers.is_empty() {
+        eprintln!("Decoder key: {}", decoder_key(&packets, dividers));
+    }
+}
+
+/// Product of the 1-based positions of the dividers among sorted `packets`.
+fn decoder_key(packets: &[Pac], dividers: &[Pac]) -> usize {
+    let mut ans = 1;
+    for divider in dividers {
+        let pos = packets.iter().position(|x| x == divider).unwrap() + 1;
+        ans *= pos;
+    }
+    ans
+}
+
 fn main() {
+    // Usage: day_13 [--explain] [--divider PACKET]... <input
+    //        day_13 sort [FILE] [--divider PACKET]...
+    let mut explain = false;
+    let mut sort = false;
+    let mut path = None;
+    let mut dividers = Vec::new();
+
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--explain" => explain = true,
+            "--divider" => {
+                let packet = args.next().expect("--divider needs a packet");
+                dividers.push(
+                    Pac::parse(&packet)
+                        .unwrap_or_else(|e| panic!("Can't parse divider {packet:?} {e}")),
+                );
+            }
+            "sort" if !sort => sort = true,
+            _ if sort && path.is_none() => path = Some(arg),
+            _ => panic!("Unexpected argument {arg:?}!"),
+        }
+    }
+
+    if sort {
+        sort_packets(path.as_deref(), &dividers);
+        return;
+    }
+
+    if dividers.is_empty() {
+        dividers = vec![Pac::parse("[[2]]").unwrap(), Pac::parse("[[6]]").unwrap()];
+    }
+
     let mut index = 1_usize;
     let mut ans = 0;
 
-    let dividers = vec![Pac::parse("[[2]]"), Pac::parse("[[6]]")];
     let mut packets = dividers.clone();
 
     loop {
-        let pa = Pac::parse(&gets().unwrap());
-        let pb = Pac::parse(&gets().unwrap());
-        // eprintln!("pa: {pa}");
-        // eprintln!("pb: {pb}");
-        // eprintln!("{:?}", pa.partial_cmp(&pb));
-        // eprintln!("----------");
+        let pa = read_packet();
+        let pb = read_packet();
+
+        if explain {
+            let explanation = pa.compare_explained(&pb);
+            debug_assert_eq!(explanation.ordering, pa.cmp(&pb));
+            eprintln!("== Pair {index} ==");
+            eprint!("{explanation}");
+            eprintln!(
+                "Decided by {:?} at {:?}\n",
+                explanation.reason, explanation.path
+            );
+        }
 
         if pa < pb {
             ans += index;
@@ -119,15 +751,15 @@ fn main() {
 
     packets.sort();
 
-    let mut ans_2 = 1;
-    for divider in &dividers {
-        let pos = packets.iter().position(|x| x == divider).unwrap() + 1;
-        ans_2 *= pos;
-    }
-
+    let ans_2 = decoder_key(&packets, &dividers);
     println!("Answer to the second part: {ans_2}");
 }
 
+fn read_packet() -> Pac {
+    let line = gets().expect("Expected another packet");
+    Pac::parse(&line).unwrap_or_else(|e| panic!("Can't parse {line:?} as Pac {e}"))
+}
+
 fn gets() -> Option<String> {
     let mut line = String::new();
     let count = stdin().read_line(&mut line).unwrap();
@@ -137,3 +769,26 @@ fn gets() -> Option<String> {
         None
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_errors() {
+        let err = Pac::parse("[1,").unwrap_err();
+        assert_eq!(err.pos, 3);
+        assert_eq!(
+            err.message,
+            "expected '[' or an integer, found end of input"
+        );
+
+        let deep = "[".repeat(60_000) + &"]".repeat(60_000);
+        let err = Pac::parse(&deep).unwrap_err();
+        assert_eq!(err.pos, MAX_DEPTH);
+        assert!(Json::parse(&deep).is_err());
+
+        let ok = "[".repeat(MAX_DEPTH) + &"]".repeat(MAX_DEPTH);
+        assert_eq!(Pac::parse(&ok).unwrap().to_string(), ok);
+    }
+}
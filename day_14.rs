@@ -1,8 +1,9 @@
 use std::{
     cmp::{max, min},
-    collections::HashMap,
     fmt::Display,
+    fs::File,
     io::*,
+    path::{Path, PathBuf},
 };
 
 #[derive(Debug, Copy, Clone, Eq, PartialEq)]
@@ -52,155 +53,368 @@ impl Loc {
     }
 }
 
-type World = HashMap<Loc, Cell>;
-
-#[allow(dead_code)]
-fn show_world(world: &World) {
-    let x_min = world.keys().map(|k| k.x).min().unwrap();
-    let x_max = world.keys().map(|k| k.x).max().unwrap();
-    let y_min = world.keys().map(|k| k.y).min().unwrap();
-    let y_max = world.keys().map(|k| k.y).max().unwrap();
+/// Dense grid wide enough for anything the sand can reach: a grain moves at
+/// most one column sideways per row it falls.
+struct Cave {
+    cells: Vec<Cell>,
+    x_min: i32,
+    width: usize,
+    height: usize,
+    wall_x_min: i32,
+    wall_x_max: i32,
+    wall_y_max: i32,
+    /// Row of the infinite floor, if there is one. It is never stored in `cells`.
+    floor_y: Option<i32>,
+    /// Where the previous grain fell through. Everything on it except the last
+    /// cell is still free, so the next grain can resume from there.
+    path: Vec<Loc>,
+    /// The grain moved by the last `drop_grain`: where it came to rest, or
+    /// where it was when it fell off.
+    last_grain: Option<Loc>,
+}
 
-    eprintln!("X from {x_min} to {x_max};");
-    eprintln!("Y from {y_min} to {y_max};");
+impl Cave {
+    fn new(walls: &[Loc], with_floor: bool) -> Cave {
+        let wall_y_max = walls.iter().map(|loc| loc.y).max().unwrap();
+        let height = (wall_y_max + 2) as usize;
+        let wall_x_min = walls.iter().map(|loc| loc.x).min().unwrap();
+        let wall_x_max = walls.iter().map(|loc| loc.x).max().unwrap();
+        let x_min = wall_x_min.min(SPAWNER_LOC.x) - height as i32 - 1;
+        let x_max = wall_x_max.max(SPAWNER_LOC.x) + height as i32 + 1;
+        let width = (x_max - x_min + 1) as usize;
+
+        let mut cave = Cave {
+            cells: vec![Air; width * height],
+            x_min,
+            width,
+            height,
+            wall_x_min,
+            wall_x_max,
+            wall_y_max,
+            floor_y: with_floor.then_some(wall_y_max + 2),
+            path: Vec::new(),
+            last_grain: None,
+        };
 
-    for y in y_min..=y_max {
-        for x in x_min..=x_max {
-            let cell = world.get(&Loc { x, y }).unwrap_or(&Air);
-            eprint!("{}", cell);
+        for &loc in walls {
+            cave.set(loc, Wall);
         }
-        eprintln!();
-    }
-}
-
-const SPAWNER_LOC: Loc = Loc { x: 500, y: 0 };
+        cave.set(SPAWNER_LOC, Spawner);
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
-enum SimulationResult {
-    Stabilized,
-    FallenOff,
-    BlockedSpawner,
-}
+        cave
+    }
 
-fn simulate_sand_fall(world: &mut World) -> SimulationResult {
-    // Grossly inefficient, but I don't care.
-    let wall_y_max = world
-        .iter()
-        .filter_map(|(loc, &cell)| if cell == Wall { Some(loc.y) } else { None })
-        .max()
-        .unwrap();
+    fn index(&self, loc: Loc) -> Option<usize> {
+        let x = (loc.x - self.x_min) as usize;
+        let y = loc.y as usize;
+        (loc.x >= self.x_min && loc.y >= 0 && x < self.width && y < self.height)
+            .then(|| y * self.width + x)
+    }
 
-    let mut cur = SPAWNER_LOC;
+    fn get(&self, loc: Loc) -> Cell {
+        if self.floor_y == Some(loc.y) {
+            return Wall;
+        }
+        self.index(loc).map_or(Air, |k| self.cells[k])
+    }
 
-    if world.get(&cur).unwrap_or(&Air) == &Sand {
-        return SimulationResult::BlockedSpawner;
+    fn set(&mut self, loc: Loc, cell: Cell) {
+        let k = self.index(loc).unwrap();
+        self.cells[k] = cell;
     }
 
-    let try_cell = |world: &World, cur: Loc, dx: i32, dy: i32| {
-        let next = Loc {
-            x: cur.x + dx,
-            y: cur.y + dy,
-        };
-        let next_cell = world.get(&next).unwrap_or(&Air);
-        if !next_cell.is_blocking() {
-            Some(next)
-        } else {
-            None
+    #[allow(dead_code)]
+    fn show(&self) {
+        let mut x_min = i32::MAX;
+        let mut x_max = i32::MIN;
+        let mut y_max = 0;
+        for (k, &cell) in self.cells.iter().enumerate() {
+            if cell != Air {
+                let x = self.x_min + (k % self.width) as i32;
+                x_min = min(x_min, x);
+                x_max = max(x_max, x);
+                y_max = max(y_max, (k / self.width) as i32);
+            }
         }
-    };
 
-    while cur.y <= wall_y_max {
-        if let Some(next) = try_cell(world, cur, 0, 1) {
-            cur = next;
-            continue;
+        eprintln!("X from {x_min} to {x_max};");
+        eprintln!("Y from 0 to {y_max};");
+
+        for y in 0..=y_max {
+            for x in x_min..=x_max {
+                eprint!("{}", self.get(Loc { x, y }));
+            }
+            eprintln!();
         }
+    }
 
-        if let Some(next) = try_cell(world, cur, -1, 1) {
-            cur = next;
-            continue;
+    /// With a floor, sand ends up in exactly the cells of the triangle below
+    /// the spawner that can be reached by going down or diagonally down
+    /// without crossing a wall. Counts them one row at a time, no grains needed.
+    fn count_resting_sand(&self) -> usize {
+        let floor_y = self.floor_y.expect("Closed form needs a floor");
+
+        let mut row = vec![false; self.width];
+        row[(SPAWNER_LOC.x - self.x_min) as usize] = true;
+        let mut ans = 1;
+
+        for y in (SPAWNER_LOC.y + 1)..floor_y {
+            let next: Vec<bool> = (0..self.width)
+                .map(|k| {
+                    let x = self.x_min + k as i32;
+                    let from_above = row[k.saturating_sub(1)..(k + 2).min(self.width)]
+                        .iter()
+                        .any(|&r| r);
+                    from_above && self.get(Loc { x, y }) != Wall
+                })
+                .collect();
+
+            ans += next.iter().filter(|&&r| r).count();
+            row = next;
         }
 
-        if let Some(next) = try_cell(world, cur, 1, 1) {
-            cur = next;
-            continue;
+        ans
+    }
+
+    /// Inclusive `(x_min, x_max, y_max)` of everything that can ever be worth
+    /// drawing, so that all frames of an animation line up.
+    fn frame_bounds(&self) -> (i32, i32, i32) {
+        match self.floor_y {
+            // Resting sand never sticks out past the walls holding it up.
+            None => (
+                min(self.wall_x_min, SPAWNER_LOC.x) - 1,
+                max(self.wall_x_max, SPAWNER_LOC.x) + 1,
+                self.wall_y_max + 1,
+            ),
+            // With a floor, sand fills a triangle under the spawner.
+            Some(floor_y) => (
+                min(self.wall_x_min, SPAWNER_LOC.x - floor_y) - 1,
+                max(self.wall_x_max, SPAWNER_LOC.x + floor_y) + 1,
+                floor_y,
+            ),
         }
+    }
 
-        world.insert(cur, Sand);
-        return SimulationResult::Stabilized;
+    /// Like `Cell::symbol`, but the last grain dropped is a `~`.
+    fn render(&self) -> Vec<Vec<char>> {
+        let (x_min, x_max, y_max) = self.frame_bounds();
+
+        (0..=y_max)
+            .map(|y| {
+                (x_min..=x_max)
+                    .map(|x| {
+                        let loc = Loc { x, y };
+                        if Some(loc) == self.last_grain {
+                            '~'
+                        } else {
+                            self.get(loc).symbol()
+                        }
+                    })
+                    .collect()
+            })
+            .collect()
     }
 
-    SimulationResult::FallenOff
+    fn drop_grain(&mut self) -> SimulationResult {
+        if self.path.is_empty() {
+            if self.get(SPAWNER_LOC) == Sand {
+                return SimulationResult::BlockedSpawner;
+            }
+            self.path.push(SPAWNER_LOC);
+        }
+
+        loop {
+            let cur = *self.path.last().unwrap();
+            if self.floor_y.is_none() && cur.y > self.wall_y_max {
+                self.last_grain = Some(cur);
+                return SimulationResult::FallenOff;
+            }
+
+            let next = [0, -1, 1]
+                .into_iter()
+                .map(|dx| Loc {
+                    x: cur.x + dx,
+                    y: cur.y + 1,
+                })
+                .find(|&next| !self.get(next).is_blocking());
+
+            match next {
+                Some(next) => self.path.push(next),
+                None => {
+                    self.set(cur, Sand);
+                    self.last_grain = Some(cur);
+                    self.path.pop();
+                    return SimulationResult::Stabilized;
+                }
+            }
+        }
+    }
 }
 
-fn main() {
-    let mut world = World::new();
-    world.insert(SPAWNER_LOC, Spawner);
+/// Writes a frame of the cave every `every` grains, either as numbered PPM
+/// images or all into one text file.
+struct Animation {
+    dir: PathBuf,
+    every: usize,
+    ascii: bool,
+}
 
-    while let Some(line) = gets() {
-        // eprintln!("Processing {line:?} ...");
-        let points: Vec<Loc> = line.split(" -> ").map(Loc::parse).collect();
+impl Animation {
+    fn record(&self, name: &str, cave: &mut Cave) -> Result<usize> {
+        std::fs::create_dir_all(&self.dir)?;
+        let mut ascii_out = if self.ascii {
+            let file = File::create(self.dir.join(format!("{name}.txt")))?;
+            Some(BufWriter::new(file))
+        } else {
+            None
+        };
 
-        for (&pa, &pb) in points.iter().zip(points.iter().skip(1)) {
-            // eprintln!("{pa:?} --> {pb:?}");
+        let mut grains = 0_usize;
+        let mut frame = 0;
+        loop {
+            let done = cave.drop_grain() != SimulationResult::Stabilized;
+            if !done {
+                grains += 1;
+            }
 
-            if pa.x == pb.x {
-                let x = pa.x;
-                let y_min = min(pa.y, pb.y);
-                let y_max = max(pa.y, pb.y);
-                for y in y_min..=y_max {
-                    world.insert(Loc { x, y }, Wall);
-                }
+            // The last grain may already have been drawn by the regular frames.
+            let due = if done {
+                frame == 0 || !grains.is_multiple_of(self.every)
             } else {
-                assert_eq!(pa.y, pb.y);
-                let y = pa.y;
-                let x_min = min(pa.x, pb.x);
-                let x_max = max(pa.x, pb.x);
-                for x in x_min..=x_max {
-                    world.insert(Loc { x, y }, Wall);
+                grains.is_multiple_of(self.every)
+            };
+
+            if due {
+                let pixels = cave.render();
+                match &mut ascii_out {
+                    Some(out) => {
+                        writeln!(out, "== {name}, frame {frame}, {grains} grains ==")?;
+                        for row in &pixels {
+                            writeln!(out, "{}", row.iter().collect::<String>())?;
+                        }
+                        writeln!(out)?;
+                    }
+                    None => {
+                        let path = self.dir.join(format!("{name}_{frame:05}.ppm"));
+                        write_ppm(&path, &pixels)?;
+                    }
                 }
+                frame += 1;
+            }
+
+            if done {
+                break;
             }
         }
+
+        if let Some(out) = &mut ascii_out {
+            out.flush()?;
+        }
+        eprintln!("Wrote {frame} frames of {name}.");
+        Ok(grains)
     }
+}
 
-    // show_world(&world);
+fn write_ppm(path: &Path, pixels: &[Vec<char>]) -> Result<()> {
+    let mut out = BufWriter::new(File::create(path)?);
+    write!(out, "P6\n{} {}\n255\n", pixels[0].len(), pixels.len())?;
+    for &c in pixels.iter().flatten() {
+        let rgb: [u8; 3] = match c {
+            '#' => [110, 110, 120],
+            'o' => [230, 190, 90],
+            '~' => [255, 70, 40],
+            '+' => [80, 200, 255],
+            _ => [15, 15, 25],
+        };
+        out.write_all(&rgb)?;
+    }
+    out.flush()
+}
 
-    let mut ans = 0_usize;
+const SPAWNER_LOC: Loc = Loc { x: 500, y: 0 };
 
-    while simulate_sand_fall(&mut world) != SimulationResult::FallenOff {
-        ans += 1;
-        // show_world(&world);
-    }
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum SimulationResult {
+    Stabilized,
+    FallenOff,
+    BlockedSpawner,
+}
 
-    println!("Answer to the first part: {ans}");
+fn main() {
+    // Usage: day_14 [--frames <dir> [--every K] [--ascii]] <input
+    let mut frames_dir = None;
+    let mut every = 1;
+    let mut ascii = false;
+
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().unwrap_or_else(|| panic!("{arg} needs a value"));
+        match arg.as_str() {
+            "--frames" => frames_dir = Some(PathBuf::from(value())),
+            "--every" => every = value().parse().unwrap(),
+            "--ascii" => ascii = true,
+            _ => panic!("Unexpected argument {arg:?}!"),
+        }
+    }
+    assert!(every > 0, "--every needs a positive number of grains");
 
-    world = world
-        .into_iter()
-        .filter(|(_, cell)| cell == &Wall || cell == &Spawner)
-        .collect();
+    let animation = frames_dir.map(|dir| Animation { dir, every, ascii });
 
-    let floor_y = world
-        .iter()
-        .filter_map(|(loc, &cell)| if cell == Wall { Some(loc.y) } else { None })
-        .max()
-        .unwrap()
-        + 2;
+    let mut walls = Vec::new();
 
-    for x in -10_000..=10_000 {
-        world.insert(Loc { x, y: floor_y }, Wall);
+    while let Some(line) = gets() {
+        // eprintln!("Processing {line:?} ...");
+        add_walls(&mut walls, &line);
     }
 
-    eprintln!("Made \"infinite\" floor. Starting another set of simulations.");
-    eprintln!("Be patient. It may take a few minutes of wall clock time.");
+    let mut cave = Cave::new(&walls, false);
+    // cave.show();
 
-    ans = 0;
-    while simulate_sand_fall(&mut world) == SimulationResult::Stabilized {
-        ans += 1;
-    }
+    let mut ans = 0_usize;
 
-    if simulate_sand_fall(&mut world) == SimulationResult::FallenOff {
-        println!("Error! Floor is not long enough! :o(");
+    if let Some(animation) = &animation {
+        ans = animation.record("part1", &mut cave).unwrap();
     } else {
-        println!("Answer to the second part: {ans}");
+        while cave.drop_grain() == SimulationResult::Stabilized {
+            ans += 1;
+            // cave.show();
+        }
+    }
+
+    println!("Answer to the first part: {ans}");
+
+    let mut cave = Cave::new(&walls, true);
+    ans = match &animation {
+        Some(animation) => animation.record("part2", &mut cave).unwrap(),
+        None => cave.count_resting_sand(),
+    };
+
+    println!("Answer to the second part: {ans}");
+}
+
+/// Adds every cell of one "x,y -> x,y -> ..." rock path.
+fn add_walls(walls: &mut Vec<Loc>, line: &str) {
+    let points: Vec<Loc> = line.split(" -> ").map(Loc::parse).collect();
+
+    for (&pa, &pb) in points.iter().zip(points.iter().skip(1)) {
+        // eprintln!("{pa:?} --> {pb:?}");
+
+        if pa.x == pb.x {
+            let x = pa.x;
+            let y_min = min(pa.y, pb.y);
+            let y_max = max(pa.y, pb.y);
+            for y in y_min..=y_max {
+                walls.push(Loc { x, y });
+            }
+        } else {
+            assert_eq!(pa.y, pb.y);
+            let y = pa.y;
+            let x_min = min(pa.x, pb.x);
+            let x_max = max(pa.x, pb.x);
+            for x in x_min..=x_max {
+                walls.push(Loc { x, y });
+            }
+        }
     }
 }
 
@@ -213,3 +427,85 @@ fn gets() -> Option<String> {
         None
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EXAMPLE: &str = "498,4 -> 498,6 -> 496,6
+503,4 -> 502,4 -> 502,9 -> 494,9";
+
+    fn walls_of(input: &str) -> Vec<Loc> {
+        let mut walls = Vec::new();
+        for line in input.lines() {
+            add_walls(&mut walls, line);
+        }
+        walls
+    }
+
+    fn simulate(walls: &[Loc], with_floor: bool) -> usize {
+        let mut cave = Cave::new(walls, with_floor);
+        let mut grains = 0;
+        while cave.drop_grain() == SimulationResult::Stabilized {
+            grains += 1;
+        }
+        grains
+    }
+
+    /// Random rock paths in the usual range below the spawner.
+    fn generated_cave(mut seed: u64, paths: usize) -> String {
+        let mut next = |range: i32| {
+            // xorshift64
+            seed ^= seed << 13;
+            seed ^= seed >> 7;
+            seed ^= seed << 17;
+            (seed % range as u64) as i32
+        };
+
+        let mut lines = Vec::new();
+        for _ in 0..paths {
+            let (mut x, mut y) = (470 + next(61), 2 + next(39));
+            let mut points = vec![format!("{x},{y}")];
+            for k in 0..1 + next(4) {
+                if k % 2 == 0 {
+                    x += next(17) - 8;
+                } else {
+                    y = (y + next(11) - 5).max(1);
+                }
+                points.push(format!("{x},{y}"));
+            }
+            lines.push(points.join(" -> "));
+        }
+        lines.join("\n")
+    }
+
+    #[test]
+    fn example() {
+        let walls = walls_of(EXAMPLE);
+        assert_eq!(simulate(&walls, false), 24);
+        assert_eq!(simulate(&walls, true), 93);
+        assert_eq!(Cave::new(&walls, true).count_resting_sand(), 93);
+    }
+
+    #[test]
+    fn flood_fill_matches_simulation() {
+        for seed in 1..=4 {
+            let walls = walls_of(&generated_cave(seed * 0x9e37_79b9, 20 + 25 * seed as usize));
+            assert_eq!(
+                Cave::new(&walls, true).count_resting_sand(),
+                simulate(&walls, true),
+                "Generated cave #{seed}"
+            );
+        }
+    }
+
+    #[test]
+    fn render_shows_spawner_and_last_grain() {
+        let mut cave = Cave::new(&walls_of("510,3 -> 515,3"), false);
+        assert_eq!(cave.drop_grain(), SimulationResult::FallenOff);
+
+        let pixels = cave.render();
+        assert_eq!(pixels[0][1], '+');
+        assert_eq!(pixels[4][1], '~');
+    }
+}
@@ -1,6 +1,6 @@
 use lazy_static::lazy_static;
 use regex::Regex;
-use std::{collections::HashSet, io::*};
+use std::{collections::HashSet, fmt::Display, fs::File, io::*, path::Path};
 
 #[derive(Debug, Clone, Eq, PartialEq, Hash)]
 struct Loc {
@@ -32,6 +32,10 @@ impl Sensor {
         Sensor { loc, radius }
     }
 
+    fn covers(&self, loc: &Loc) -> bool {
+        self.loc.dist(loc) <= self.radius
+    }
+
     fn contains_y(&self, y: i64) -> bool {
         (self.loc.y - y).abs() <= self.radius
     }
@@ -47,7 +51,62 @@ impl Sensor {
     }
 }
 
+/// Merges overlapping or touching intervals; the result is sorted and disjoint.
+fn merge(mut lines: Vec<Line>) -> Vec<Line> {
+    lines.sort_by_key(|line| line.l);
+
+    let mut merged: Vec<Line> = Vec::new();
+    for line in lines {
+        match merged.last_mut() {
+            Some(last) if line.l <= last.r + 1 => last.r = last.r.max(line.r),
+            _ => merged.push(line),
+        }
+    }
+    merged
+}
+
+/// Number of cells on row `y` that are covered by some sensor and aren't
+/// already taken by a sensor or a beacon.
+fn covered_on_row(sensors: &[Sensor], blocked: &HashSet<Loc>, y: i64) -> i64 {
+    let lines = sensors
+        .iter()
+        .filter(|s| s.contains_y(y))
+        .map(|s| s.line_at(y))
+        .collect();
+    let merged = merge(lines);
+
+    let covered: i64 = merged.iter().map(|line| line.r - line.l + 1).sum();
+    let taken = blocked
+        .iter()
+        .filter(|loc| loc.y == y && merged.iter().any(|line| line.l <= loc.x && loc.x <= line.r))
+        .count() as i64;
+
+    covered - taken
+}
+
 fn main() {
+    // Usage: day_15 [LINE_Y [MAX_COORD]] [--report <dir> [--pixels N]] <input
+    // The example needs `day_15 10 20`.
+    let mut report_dir = None;
+    let mut pixels = 512;
+    let mut args = Vec::<i64>::new();
+
+    let mut raw_args = std::env::args().skip(1);
+    while let Some(arg) = raw_args.next() {
+        let mut value = || {
+            raw_args
+                .next()
+                .unwrap_or_else(|| panic!("{arg} needs a value"))
+        };
+        match arg.as_str() {
+            "--report" => report_dir = Some(value()),
+            "--pixels" => pixels = value().parse().unwrap(),
+            _ => args.push(arg.parse().expect("Arguments must be integers")),
+        }
+    }
+    let line_y = args.first().copied().unwrap_or(2_000_000);
+    let max_coord = args.get(1).copied().unwrap_or(4_000_000);
+
     let mut sensors = Vec::new();
     let mut blocked = HashSet::new();
 
@@ -77,93 +136,320 @@ fn main() {
 
     // eprintln!("Sensors: {sensors:#?}");
 
-    const LINE_Y: i64 = 2000000;
+    let ans = covered_on_row(&sensors, &blocked, line_y);
+    println!("Answer to the first part: {ans}");
 
-    let min_x = sensors.iter().map(|s| s.loc.x - s.radius).min().unwrap();
-    let max_x = sensors.iter().map(|s| s.loc.x + s.radius).max().unwrap();
-    // eprintln!("min_x: {min_x}");
-    // eprintln!("max_x: {max_x}");
+    if let Some(dir) = report_dir {
+        let report = coverage_report(&sensors, max_coord);
+        print!("{report}");
 
-    let mut ans = 0_usize;
-    for x in min_x..=max_x {
-        let cur = Loc { x, y: LINE_Y };
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = Path::new(&dir).join("coverage.ppm");
+        write_coverage_ppm(&path, &sensors, &blocked, &report, pixels).unwrap();
+        eprintln!("Wrote {}", path.display());
+    }
 
-        if blocked.contains(&cur) {
-            continue;
+    match find_distress_beacon(&sensors, max_coord) {
+        Ok(Loc { x, y }) => {
+            println!("Found it! x = {x}, y = {y}");
+            println!("Answer to the second part: {}", x * 4_000_000 + y);
         }
+        Err(e) => println!("Part two failed: {e}"),
+    }
+}
 
-        if sensors.iter().any(|s| s.loc.dist(&cur) <= s.radius) {
-            ans += 1;
+/// The only free cell in the box is boxed in by diamonds (and maybe the box
+/// edges), so it lies where the lines just outside the diamonds cross.
+/// In rotated coordinates `u = x + y`, `v = x - y` those lines are
+/// `u = su ± (r + 1)` and `v = sv ± (r + 1)`, which makes crossing them trivial.
+fn find_distress_beacon(sensors: &[Sensor], max_coord: i64) -> std::result::Result<Loc, String> {
+    let mut us = Vec::new();
+    let mut vs = Vec::new();
+    for s in sensors {
+        let (su, sv) = (s.loc.x + s.loc.y, s.loc.x - s.loc.y);
+        us.extend([su - s.radius - 1, su + s.radius + 1]);
+        vs.extend([sv - s.radius - 1, sv + s.radius + 1]);
+    }
+
+    let mut candidates = Vec::new();
+    for &u in &us {
+        for &v in &vs {
+            if (u + v) % 2 == 0 {
+                candidates.push(Loc {
+                    x: (u + v) / 2,
+                    y: (u - v) / 2,
+                });
+            }
         }
     }
 
-    println!("Answer to the first part: {ans}");
+    // A gap pressed against the edge of the box only needs one line.
+    for e in [0, max_coord] {
+        for &u in &us {
+            candidates.push(Loc { x: e, y: u - e });
+            candidates.push(Loc { x: u - e, y: e });
+        }
+        for &v in &vs {
+            candidates.push(Loc { x: e, y: e - v });
+            candidates.push(Loc { x: v + e, y: e });
+        }
+        candidates.push(Loc { x: e, y: 0 });
+        candidates.push(Loc { x: e, y: max_coord });
+    }
 
-    const MAX_COORD: i64 = 4_000_000;
-
-    // Let's visualize this stuff:
-    // for y in 0..21 {
-    //     for x in 0..21 {
-    //         let cur = Loc { x, y };
-    //
-    //         if blocked.contains(&cur) {
-    //             eprint!("B");
-    //             continue;
-    //         }
-    //
-    //         if sensors.iter().any(|s| s.loc.dist(&cur) <= s.radius) {
-    //             eprint!("#");
-    //         } else {
-    //             eprint!(".");
-    //         }
-    //     }
-    //     eprintln!();
-    // }
-
-    for y in 0..MAX_COORD {
-        // eprintln!("y = {y} ...");
-
-        let mut lines: Vec<Line> = sensors
-            .iter()
-            .filter(|s| s.contains_y(y))
-            .filter_map(|s| {
-                let mut line = s.line_at(y);
-                line.l = line.l.max(0);
-                if line.r >= 0 {
-                    Some(line)
+    let in_box = |loc: &Loc| (0..=max_coord).contains(&loc.x) && (0..=max_coord).contains(&loc.y);
+    let mut gaps: Vec<Loc> = candidates
+        .into_iter()
+        .filter(|loc| in_box(loc) && !sensors.iter().any(|s| s.covers(loc)))
+        .collect();
+    gaps.sort_by_key(|loc| (loc.x, loc.y));
+    gaps.dedup();
+
+    match gaps.len() {
+        0 => Err("No uncovered cell found".to_string()),
+        1 => Ok(gaps.pop().unwrap()),
+        n => Err(format!(
+            "{n} uncovered cells found, e.g. {:?} and {:?}",
+            gaps[0], gaps[1]
+        )),
+    }
+}
+
+/// Rectangle in rotated coordinates `u = x + y`, `v = x - y`, bounds inclusive.
+/// Only cells with `u` and `v` of the same parity correspond to real cells.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+struct RotRect {
+    u0: i64,
+    u1: i64,
+    v0: i64,
+    v1: i64,
+}
+
+impl RotRect {
+    /// Real cells of the rectangle inside the `[0, max_coord]²` box.
+    fn cells_in_box(&self, max_coord: i64) -> impl Iterator<Item = Loc> + '_ {
+        let (u0, u1) = (self.u0.max(0), self.u1.min(2 * max_coord));
+        (u0..=u1).flat_map(move |u| {
+            let (lo, hi) = self.column_in_box(u, max_coord);
+            (lo..=hi).step_by(2).map(move |v| Loc {
+                x: (u + v) / 2,
+                y: (u - v) / 2,
+            })
+        })
+    }
+
+    fn count_in_box(&self, max_coord: i64) -> i64 {
+        let (u0, u1) = (self.u0.max(0), self.u1.min(2 * max_coord));
+        (u0..=u1)
+            .map(|u| {
+                let (lo, hi) = self.column_in_box(u, max_coord);
+                if lo > hi {
+                    0
                 } else {
-                    None
+                    (hi - lo) / 2 + 1
                 }
             })
+            .sum()
+    }
+
+    /// First and last `v` of column `u` that are real cells inside the box.
+    /// Empty if `lo > hi`.
+    fn column_in_box(&self, u: i64, max_coord: i64) -> (i64, i64) {
+        let lo = self.v0.max(-u).max(u - 2 * max_coord);
+        let hi = self.v1.min(u).min(2 * max_coord - u);
+        let lo = lo + (lo - u).rem_euclid(2);
+        (lo, hi)
+    }
+}
+
+struct CoverageReport {
+    max_coord: i64,
+    covered: i64,
+    /// Uncovered parts of the box with the number of real cells in each.
+    uncovered: Vec<(RotRect, i64)>,
+}
+
+impl Display for CoverageReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let total = (self.max_coord + 1) * (self.max_coord + 1);
+        writeln!(
+            f,
+            "Sensors cover {} of {} cells in [0, {}]² ({:.6}%).",
+            self.covered,
+            total,
+            self.max_coord,
+            100.0 * self.covered as f64 / total as f64
+        )?;
+        writeln!(f, "Uncovered regions (u = x + y, v = x - y):")?;
+        for (rect, count) in &self.uncovered {
+            let first = rect.cells_in_box(self.max_coord).next().unwrap();
+            writeln!(
+                f,
+                "  u {}..={}, v {}..={}: {count} cells, first at x = {}, y = {}",
+                rect.u0, rect.u1, rect.v0, rect.v1, first.x, first.y
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// In rotated coordinates every diamond becomes an axis-aligned square, so
+/// sweeping along `u` only needs the squares' `v` intervals. Between two
+/// square edges the set of gaps doesn't change, which gives the rectangles.
+fn coverage_report(sensors: &[Sensor], max_coord: i64) -> CoverageReport {
+    let (u_max, v_min, v_max) = (2 * max_coord, -max_coord, max_coord);
+    let squares: Vec<RotRect> = sensors
+        .iter()
+        .map(|s| {
+            let (u, v) = (s.loc.x + s.loc.y, s.loc.x - s.loc.y);
+            RotRect {
+                u0: u - s.radius,
+                u1: u + s.radius,
+                v0: v - s.radius,
+                v1: v + s.radius,
+            }
+        })
+        .collect();
+
+    let mut edges: Vec<i64> = squares
+        .iter()
+        .flat_map(|sq| [sq.u0, sq.u1 + 1])
+        .chain([0, u_max + 1])
+        .filter(|&u| (0..=u_max + 1).contains(&u))
+        .collect();
+    edges.sort();
+    edges.dedup();
+
+    let mut uncovered = Vec::new();
+    let mut open: Vec<(Line, i64)> = Vec::new();
+
+    for slab in edges.windows(2) {
+        let u = slab[0];
+        let active = squares
+            .iter()
+            .filter(|sq| sq.u0 <= u && u <= sq.u1)
+            .map(|sq| Line { l: sq.v0, r: sq.v1 })
             .collect();
 
-        for loc in &blocked {
-            if loc.y == y {
-                lines.push(Line { l: loc.x, r: loc.x });
+        let mut gaps = Vec::new();
+        let mut front = v_min;
+        for line in merge(active) {
+            if line.l > front {
+                gaps.push(Line {
+                    l: front,
+                    r: (line.l - 1).min(v_max),
+                });
             }
+            front = front.max(line.r + 1);
+        }
+        if front <= v_max {
+            gaps.push(Line { l: front, r: v_max });
         }
+        gaps.retain(|gap| gap.l <= gap.r);
 
-        lines.sort_by_key(|line| line.l);
+        for (gap, u0) in std::mem::take(&mut open) {
+            if gaps.contains(&gap) {
+                open.push((gap, u0));
+            } else {
+                uncovered.push(RotRect {
+                    u0,
+                    u1: u - 1,
+                    v0: gap.l,
+                    v1: gap.r,
+                });
+            }
+        }
+        for gap in gaps {
+            if !open.iter().any(|(g, _)| g == &gap) {
+                open.push((gap, u));
+            }
+        }
+    }
+    for (gap, u0) in open {
+        uncovered.push(RotRect {
+            u0,
+            u1: u_max,
+            v0: gap.l,
+            v1: gap.r,
+        });
+    }
 
-        // eprintln!("lines: {lines:?}");
+    let mut uncovered: Vec<(RotRect, i64)> = uncovered
+        .into_iter()
+        .map(|rect| (rect, rect.count_in_box(max_coord)))
+        .filter(|&(_, count)| count > 0)
+        .collect();
+    uncovered.sort_by_key(|(rect, _)| (rect.u0, rect.v0));
 
-        let mut front = 0;
-        for line in lines {
-            if line.l > front {
-                let x = front;
-                if !(0..=MAX_COORD).contains(&x) {
-                    continue;
-                }
+    let total = (max_coord + 1) * (max_coord + 1);
+    let covered = total - uncovered.iter().map(|(_, count)| count).sum::<i64>();
+
+    CoverageReport {
+        max_coord,
+        covered,
+        uncovered,
+    }
+}
+
+/// Downscaled picture of the box: brighter green where more sensors overlap,
+/// black where a sampled cell is uncovered, red for small uncovered regions
+/// that sampling would miss, white sensors and yellow beacons.
+fn write_coverage_ppm(
+    path: &Path,
+    sensors: &[Sensor],
+    blocked: &HashSet<Loc>,
+    report: &CoverageReport,
+    pixels: i64,
+) -> Result<()> {
+    const SMALL_REGION: i64 = 10_000;
+
+    let size = report.max_coord + 1;
+    let cell = (size + pixels - 1) / pixels;
+    let side = (size + cell - 1) / cell;
+    let to_pixel = |loc: &Loc| -> Option<usize> {
+        let inside = (0..size).contains(&loc.x) && (0..size).contains(&loc.y);
+        inside.then(|| ((loc.y / cell) * side + loc.x / cell) as usize)
+    };
 
-                // println!("Found a gap! line = {line:?}, front = {front}");
-                println!("Found it! x = {x}, y = {y}");
-                println!("Answer to the second part: {}", x * 4_000_000 + y);
-                break;
+    let mut image = vec![[0_u8; 3]; (side * side) as usize];
+    for py in 0..side {
+        for px in 0..side {
+            let sample = Loc {
+                x: (px * cell + cell / 2).min(size - 1),
+                y: (py * cell + cell / 2).min(size - 1),
+            };
+            let overlap = sensors.iter().filter(|s| s.covers(&sample)).count() as u8;
+            if overlap > 0 {
+                image[(py * side + px) as usize] = [0, 60 + 40 * overlap.min(4), 30];
             }
+        }
+    }
 
-            front = front.max(line.r + 1);
+    for (rect, count) in &report.uncovered {
+        if *count <= SMALL_REGION {
+            for loc in rect.cells_in_box(report.max_coord) {
+                image[to_pixel(&loc).unwrap()] = [255, 40, 40];
+            }
         }
     }
+
+    for loc in blocked {
+        if let Some(k) = to_pixel(loc) {
+            let is_sensor = sensors.iter().any(|s| &s.loc == loc);
+            image[k] = if is_sensor {
+                [255, 255, 255]
+            } else {
+                [255, 220, 0]
+            };
+        }
+    }
+
+    let mut out = BufWriter::new(File::create(path)?);
+    write!(out, "P6\n{side} {side}\n255\n")?;
+    for rgb in &image {
+        out.write_all(rgb)?;
+    }
+    out.flush()
 }
 
 fn gets() -> Option<String> {
@@ -2,15 +2,18 @@
 use lazy_static::lazy_static;
 use regex::Regex;
 use std::{
-    collections::{HashMap, HashSet, VecDeque},
+    collections::{HashMap, VecDeque},
+    fmt::Display,
     fs::File,
     io::*,
     process::Command,
+    str::FromStr,
 };
 
 #[derive(Debug, Eq, PartialEq)]
 struct Valve {
     index: usize,
+    name: String,
     flow: u64,
     tunnels: Vec<usize>,
 }
@@ -19,8 +22,19 @@ struct Valve {
 struct Graph {
     valves: Vec<Valve>,
     indices: HashMap<String, usize>,
-    dist: Vec<Vec<usize>>,
-    non_zero: Vec<usize>,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+struct ParseError {
+    /// 1-based line number in the input.
+    line: usize,
+    message: String,
+}
+
+impl Display for ParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "on line {}: {}", self.line, self.message)
+    }
 }
 
 impl Graph {
@@ -36,6 +50,7 @@ impl Graph {
             self.indices.insert(name.to_string(), index);
             self.valves.push(Valve {
                 index,
+                name: name.to_string(),
                 flow: 0,
                 tunnels: vec![],
             });
@@ -75,46 +90,6 @@ impl Graph {
         self.valves[a].tunnels.push(b);
     }
 
-    fn parse() -> Graph {
-        let mut g = Graph::new();
-
-        while let Some(line) = gets() {
-            lazy_static! {
-                static ref RE: Regex = Regex::new(
-                    r"^Valve (\w+) has flow rate=(\d+); tunnels? leads? to valves? ([\w\s,]+)$"
-                )
-                .unwrap();
-            }
-
-            let caps = RE.captures(&line).unwrap();
-
-            let name = &caps[1];
-            let flow: u64 = caps[2].parse().unwrap();
-            let mut neighbors: Vec<&str> = caps[3].split(',').map(|s| s.trim()).collect();
-            neighbors.sort();
-
-            g.add_valve(name);
-            g.set_flow(name, flow);
-
-            for nbr in neighbors {
-                g.add_valve(nbr);
-                g.add_edge(name, nbr);
-            }
-        }
-
-        g.compute_dist();
-
-        g.non_zero = g
-            .valves
-            .iter()
-            .enumerate()
-            .filter(|&(_, v)| v.flow > 0)
-            .map(|(i, _)| i)
-            .collect();
-
-        g
-    }
-
     fn write_dot(&self, f: &mut impl Write) -> std::io::Result<()> {
         writeln!(f, "strict graph G {{")?;
         // writeln!(f, "    rankdir=\"LR\";")?;
@@ -164,13 +139,126 @@ impl Graph {
         dist
     }
 
-    fn compute_dist(&mut self) {
-        let n = self.valves.len();
+    /// Boils the graph down to the valves worth opening and `start`.
+    fn network(&self, start: &str) -> Option<Network> {
+        let start = self.index_of(start)?;
+        let from_start = self.bfs(start);
+
+        // Valves we can't walk to might as well not exist.
+        let mut nodes: Vec<usize> = self
+            .valves
+            .iter()
+            .filter(|v| v.flow > 0 && from_start[v.index] != usize::MAX)
+            .map(|v| v.index)
+            .collect();
+        let flow = nodes.iter().map(|&i| self.valves[i].flow).collect();
+        nodes.push(start);
+
+        let dist = nodes
+            .iter()
+            .map(|&i| {
+                let dist = self.bfs(i);
+                nodes
+                    .iter()
+                    .map(|&j| match dist[j] {
+                        usize::MAX => u64::MAX,
+                        d => d as u64,
+                    })
+                    .collect()
+            })
+            .collect();
 
-        self.dist = vec![Vec::new(); n];
-        for i in 0..n {
-            self.dist[i] = self.bfs(i);
+        Some(Network { nodes, flow, dist })
+    }
+}
+
+impl FromStr for Graph {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> std::result::Result<Graph, ParseError> {
+        lazy_static! {
+            static ref RE: Regex = Regex::new(
+                r"^Valve (\w+) has flow rate=(\d+); tunnels? leads? to valves? ([\w\s,]+)$"
+            )
+            .unwrap();
         }
+
+        let mut g = Graph::new();
+        let mut tunnels = Vec::new();
+
+        for (k, line) in s.lines().map(|l| l.trim()).enumerate() {
+            if line.is_empty() {
+                continue;
+            }
+            let error = |message: String| ParseError {
+                line: k + 1,
+                message,
+            };
+
+            let caps = RE
+                .captures(line)
+                .ok_or_else(|| error(format!("can't make sense of {line:?}")))?;
+
+            let name = &caps[1];
+            let flow: u64 = caps[2]
+                .parse()
+                .map_err(|e| error(format!("bad flow rate {:?}: {e}", &caps[2])))?;
+            let mut neighbors: Vec<&str> = caps[3].split(',').map(|s| s.trim()).collect();
+            neighbors.sort();
+
+            if g.index_of(name).is_some() {
+                return Err(error(format!("valve {name} is described twice")));
+            }
+            g.add_valve(name);
+            g.set_flow(name, flow);
+
+            let neighbors: Vec<String> = neighbors.into_iter().map(String::from).collect();
+            tunnels.push((k + 1, name.to_string(), neighbors));
+        }
+
+        // Tunnels may lead to valves described further down.
+        for (line, name, neighbors) in tunnels {
+            for nbr in neighbors {
+                if g.index_of(&nbr).is_none() {
+                    return Err(ParseError {
+                        line,
+                        message: format!("tunnel from {name} leads to unknown valve {nbr}"),
+                    });
+                }
+                g.add_edge(&name, &nbr);
+            }
+        }
+
+        Ok(g)
+    }
+}
+
+/// The part of the graph the search cares about: the valves worth opening,
+/// followed by the start valve, with walking times between all of them.
+/// Everything below works with indices into `nodes`.
+#[derive(Debug)]
+struct Network {
+    /// Indices into `Graph::valves`.
+    nodes: Vec<usize>,
+    /// Flow of each valve worth opening, so one shorter than `nodes`.
+    flow: Vec<u64>,
+    /// `u64::MAX` if there's no way from one valve to the other.
+    dist: Vec<Vec<u64>>,
+}
+
+impl Network {
+    /// Number of valves worth opening.
+    fn len(&self) -> usize {
+        self.flow.len()
+    }
+
+    fn start(&self) -> usize {
+        self.nodes.len() - 1
+    }
+
+    /// Time to travel from `i` to valve `j` and to open it.
+    fn need_time(&self, i: usize, j: usize) -> u64 {
+        self.dist[i][j].saturating_add(1)
     }
 }
 
@@ -195,25 +283,54 @@ fn show_graph(g: &Graph) {
     //     .expect("Failed to run sxiv.");
 }
 
-#[derive(Debug, Default, Copy, Clone, Eq, PartialEq, Hash)]
-struct BitMask(u64);
+/// Set of valves worth opening, as many bits long as it needs to be.
+/// Trailing zero words are never stored, so equal sets compare equal.
+#[derive(Debug, Default, Clone, Eq, PartialEq, Hash)]
+struct BitMask(Vec<u64>);
 
 impl BitMask {
-    const BIT_COUNT: usize = 64;
+    const WORD_BITS: usize = 64;
 
     fn all_set(bit_count: usize) -> Self {
-        Self((1 << bit_count) - 1)
+        let mut words = vec![u64::MAX; bit_count / Self::WORD_BITS];
+        let rest = bit_count % Self::WORD_BITS;
+        if rest > 0 {
+            words.push((1 << rest) - 1);
+        }
+        Self(words)
+    }
+
+    /// Set made of the bits of a subset table index.
+    fn from_index(index: usize) -> Self {
+        let mut mask = Self::default();
+        for bit in 0..usize::BITS as usize {
+            mask.set(bit, (index >> bit) & 1 == 1);
+        }
+        mask
+    }
+
+    fn is_disjoint(&self, other: &BitMask) -> bool {
+        self.0.iter().zip(&other.0).all(|(a, b)| a & b == 0)
     }
 
     fn is_set(&self, bit: usize) -> bool {
-        ((self.0 >> bit) & 1) == 1
+        self.0
+            .get(bit / Self::WORD_BITS)
+            .is_some_and(|word| ((word >> (bit % Self::WORD_BITS)) & 1) == 1)
     }
 
     fn set(&mut self, bit: usize, value: bool) {
-        self.0 = if value {
-            self.0 | (1 << bit)
-        } else {
-            self.0 & !(1 << bit)
+        let (word, bit) = (bit / Self::WORD_BITS, bit % Self::WORD_BITS);
+        if value {
+            if word >= self.0.len() {
+                self.0.resize(word + 1, 0);
+            }
+            self.0[word] |= 1 << bit;
+        } else if word < self.0.len() {
+            self.0[word] &= !(1 << bit);
+            while self.0.last() == Some(&0) {
+                self.0.pop();
+            }
         }
     }
 
@@ -248,7 +365,7 @@ impl Iterator for BitMaskIter {
     type Item = usize;
 
     fn next(&mut self) -> Option<Self::Item> {
-        while self.bit < BitMask::BIT_COUNT {
+        while self.bit < self.mask.0.len() * BitMask::WORD_BITS {
             self.bit += 1;
             if self.mask.is_set(self.bit - 1) {
                 return Some(self.bit - 1);
@@ -270,6 +387,7 @@ impl IntoIterator for BitMask {
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 struct Key {
+    /// Where we stand, as an index into `Network::nodes`.
     start_index: usize,
     remaining: BitMask,
     time_left: u64,
@@ -283,7 +401,7 @@ struct Stats {
     total: usize,
 }
 
-fn solve(g: &Graph, key: Key, cache: &mut Cache, stats: &mut Stats) -> u64 {
+fn solve(net: &Network, key: Key, cache: &mut Cache, stats: &mut Stats) -> u64 {
     stats.total += 1;
     if let Some(&ans) = cache.get(&key) {
         stats.cache_hits += 1;
@@ -294,15 +412,12 @@ fn solve(g: &Graph, key: Key, cache: &mut Cache, stats: &mut Stats) -> u64 {
     let i = key.start_index;
 
     // Let's try each non-zero valves.
-    for j_index in key.remaining.into_iter() {
-        let j = g.non_zero[j_index];
-
-        // Time to travel to valve `j` and to open it.
-        let need_time = (g.dist[i][j] + 1) as u64;
+    for j in key.remaining.clone() {
+        let need_time = net.need_time(i, j);
 
         if key.time_left > need_time {
-            let mut next_remaining = key.remaining;
-            next_remaining.set(j_index, false);
+            let mut next_remaining = key.remaining.clone();
+            next_remaining.set(j, false);
 
             let next_key = Key {
                 start_index: j,
@@ -310,8 +425,8 @@ fn solve(g: &Graph, key: Key, cache: &mut Cache, stats: &mut Stats) -> u64 {
                 time_left: key.time_left - need_time,
             };
 
-            let mut next_ans = next_key.time_left * g.valves[j].flow;
-            next_ans += solve(g, next_key, cache, stats);
+            let mut next_ans = next_key.time_left * net.flow[j];
+            next_ans += solve(net, next_key, cache, stats);
 
             ans = ans.max(next_ans);
         }
@@ -321,73 +436,387 @@ fn solve(g: &Graph, key: Key, cache: &mut Cache, stats: &mut Stats) -> u64 {
     ans
 }
 
-fn main() {
-    let g = Graph::parse();
+/// Walks the cache back from `key`, picking at each step a valve that
+/// achieves the cached optimum. Like `solve`, it only looks at `remaining`.
+fn plan(net: &Network, mut key: Key, cache: &mut Cache, stats: &mut Stats) -> Vec<Opening> {
+    let mut openings = Vec::new();
+    let mut best = solve(net, key.clone(), cache, stats);
+
+    while best > 0 {
+        let i = key.start_index;
+        let (next_key, pressure) = key
+            .remaining
+            .clone()
+            .into_iter()
+            .filter_map(|j| {
+                let need_time = net.need_time(i, j);
+                if key.time_left <= need_time {
+                    return None;
+                }
 
-    let start_index = g.index_of("AA").unwrap();
-    // assert_eq!(start_index, 0);
+                let mut next_remaining = key.remaining.clone();
+                next_remaining.set(j, false);
+                let next_key = Key {
+                    start_index: j,
+                    remaining: next_remaining,
+                    time_left: key.time_left - need_time,
+                };
+                Some((next_key.clone(), next_key.time_left * net.flow[j]))
+            })
+            .find(|(next_key, pressure)| {
+                pressure + solve(net, next_key.clone(), cache, stats) == best
+            })
+            .unwrap();
+
+        openings.push(Opening {
+            actor: 0,
+            from: i,
+            valve: next_key.start_index,
+            time_left: next_key.time_left,
+            pressure,
+        });
+        best -= pressure;
+        key = next_key;
+    }
 
-    let mut cache: Cache = HashMap::new();
-    let mut stats = Stats::default();
-    let ans_1 = solve(
-        &g,
-        Key {
-            start_index,
-            remaining: BitMask::all_set(g.non_zero.len()),
-            time_left: 30,
-        },
-        &mut cache,
-        &mut stats,
+    openings
+}
+
+/// A valve being opened by one of the actors after walking there from
+/// `from`, both indices into `Network::nodes`, leaving `time_left` minutes for it to release `pressure` in total.
+#[derive(Debug, Clone)]
+struct Opening {
+    actor: usize,
+    from: usize,
+    valve: usize,
+    time_left: u64,
+    pressure: u64,
+}
+
+fn actor_name(actor: usize) -> String {
+    match actor {
+        0 => "You".to_string(),
+        1 => "The elephant".to_string(),
+        _ => format!("Elephant #{actor}"),
+    }
+}
+
+/// Prints the openings of all actors in the order they happen.
+fn print_timeline(g: &Graph, net: &Network, time_limit: u64, openings: &[Opening]) {
+    let mut openings = openings.to_vec();
+    openings.sort_by_key(|o| (std::cmp::Reverse(o.time_left), o.actor));
+
+    let mut total = 0;
+    for o in &openings {
+        let valve = &g.valves[net.nodes[o.valve]];
+        total += o.pressure;
+        println!(
+            "  minute {:>2}: {} open{} {} (from {}, rate {:>2}) -> {:>4} pressure, {:>5} so far",
+            time_limit - o.time_left,
+            actor_name(o.actor),
+            if o.actor == 0 { "" } else { "s" },
+            valve.name,
+            g.valves[net.nodes[o.from]].name,
+            valve.flow,
+            o.pressure,
+            total
+        );
+    }
+}
+
+/// Best pressure one actor can release within `time_limit` minutes for every
+/// set of opened valves, indexed by the bitmask of valves worth opening. Afterwards
+/// every entry also covers all of its subsets, so it reads as "the best one
+/// can do if only these valves are allowed".
+fn best_per_subset(net: &Network, time_limit: u64) -> Vec<u64> {
+    let m = net.len();
+    let mut best = vec![0; 1 << m];
+    visit(net, net.start(), 0, time_limit, 0, &mut best);
+
+    for bit in 0..m {
+        for mask in 0..best.len() {
+            if (mask >> bit) & 1 == 1 {
+                best[mask] = best[mask].max(best[mask ^ (1 << bit)]);
+            }
+        }
+    }
+
+    best
+}
+
+/// Whether `best_per_subset` and `split_between` are affordable with `m`
+/// valves worth opening. The table has 2^m entries, and every actor past the
+/// second goes through all 3^m pairs of a set and one of its subsets.
+fn dense_split_fits(m: usize, actors: usize) -> bool {
+    const MAX_TABLE_BITS: usize = 20;
+    const MAX_SUBSET_PAIRS: u64 = 1 << 26;
+
+    m <= MAX_TABLE_BITS
+        && (actors <= 2 || 3_u64.pow(m as u32) * (actors - 2) as u64 <= MAX_SUBSET_PAIRS)
+}
+
+/// `opened` is a subset table index rather than a `BitMask`, since the table
+/// limits the number of valves anyway.
+fn visit(net: &Network, i: usize, opened: usize, time_left: u64, pressure: u64, best: &mut [u64]) {
+    best[opened] = best[opened].max(pressure);
+
+    for j in 0..net.len() {
+        let need_time = net.need_time(i, j);
+
+        if (opened >> j) & 1 == 0 && time_left > need_time {
+            let next_time_left = time_left - need_time;
+            let next_pressure = pressure + next_time_left * net.flow[j];
+            visit(
+                net,
+                j,
+                opened | (1 << j),
+                next_time_left,
+                next_pressure,
+                best,
+            );
+        }
+    }
+}
+
+/// Shares the valves between `actors` actors working at the same time, each
+/// taking a disjoint subset. Returns the total pressure and each actor's subset.
+fn split_between(best: &[u64], actors: usize) -> (u64, Vec<usize>) {
+    assert!(actors > 0, "Need at least one actor");
+    let full = best.len() - 1;
+
+    // `teams[k][mask]` is the best that `k + 1` actors do with the valves in
+    // `mask`, along with the subset taken by the last of them. Only the full
+    // set is needed for the biggest team.
+    let mut teams: Vec<Vec<(u64, usize)>> = vec![best.iter().map(|&b| (b, 0)).collect()];
+    for k in 1..actors {
+        let prev = teams.last().unwrap();
+        let masks = if k + 1 == actors {
+            full..=full
+        } else {
+            0..=full
+        };
+        let mut next = vec![(0, 0); best.len()];
+
+        for mask in masks {
+            let mut sub = mask;
+            loop {
+                let total = prev[mask ^ sub].0 + best[sub];
+                if total > next[mask].0 {
+                    next[mask] = (total, sub);
+                }
+                if sub == 0 {
+                    break;
+                }
+                sub = (sub - 1) & mask;
+            }
+        }
+
+        teams.push(next);
+    }
+
+    let mut shares = Vec::new();
+    let mut mask = full;
+    for team in teams[1..].iter().rev() {
+        let sub = team[mask].1;
+        shares.push(sub);
+        mask ^= sub;
+    }
+    shares.push(mask);
+    shares.reverse();
+
+    (teams[actors - 1][full].0, shares)
+}
+
+/// Same as `best_per_subset`, but only for the sets one actor can actually
+/// open in time, and without the propagation to supersets. That is far fewer
+/// than all subsets when there are many valves worth opening.
+fn best_per_route(net: &Network, time_limit: u64) -> HashMap<BitMask, u64> {
+    let mut best = HashMap::new();
+    visit_routes(
+        net,
+        net.start(),
+        BitMask::default(),
+        time_limit,
+        0,
+        &mut best,
     );
-    println!("ans_1: {ans_1}");
+    best
+}
+
+fn visit_routes(
+    net: &Network,
+    i: usize,
+    opened: BitMask,
+    time_left: u64,
+    pressure: u64,
+    best: &mut HashMap<BitMask, u64>,
+) {
+    for j in 0..net.len() {
+        let need_time = net.need_time(i, j);
+
+        if !opened.is_set(j) && time_left > need_time {
+            let mut next_opened = opened.clone();
+            next_opened.set(j, true);
+            let next_time_left = time_left - need_time;
+            let next_pressure = pressure + next_time_left * net.flow[j];
+            visit_routes(net, j, next_opened, next_time_left, next_pressure, best);
+        }
+    }
+
+    let slot = best.entry(opened).or_default();
+    *slot = (*slot).max(pressure);
+}
 
-    let m = g.non_zero.len();
-    let masks_count = 1 << m;
+/// `split_between` for `best_per_route`: picks pairwise disjoint sets, best
+/// first, and stops as soon as the rest can't beat what's been found.
+fn split_sparse(best: &HashMap<BitMask, u64>, actors: usize) -> (u64, Vec<BitMask>) {
+    assert!(actors > 0, "Need at least one actor");
 
-    let mut ans_2 = 0;
-    let mut processed_masks = HashSet::new();
+    let mut routes: Vec<(u64, &BitMask)> = best.iter().map(|(mask, &b)| (b, mask)).collect();
+    routes.sort_by_key(|&(b, _)| std::cmp::Reverse(b));
 
-    for mask in 0..masks_count {
-        let inverse_mask = !mask & (masks_count - 1);
-        if processed_masks.contains(&inverse_mask) {
-            continue;
+    let mut found = (0, Vec::new());
+    let mut picked = Vec::new();
+    pick_disjoint(&routes, actors, 0, 0, &mut picked, &mut found);
+
+    if found.1.is_empty() {
+        // Nothing worth opening in time, so everybody stays put.
+        return (0, vec![BitMask::default(); actors]);
+    }
+    let shares = found.1.iter().map(|&k| routes[k].1.clone()).collect();
+    (found.0, shares)
+}
+
+fn pick_disjoint(
+    routes: &[(u64, &BitMask)],
+    actors: usize,
+    from: usize,
+    total: u64,
+    picked: &mut Vec<usize>,
+    found: &mut (u64, Vec<usize>),
+) {
+    if picked.len() == actors {
+        if total > found.0 {
+            *found = (total, picked.clone());
         }
+        return;
+    }
 
-        let sol_1 = solve(
-            &g,
-            Key {
-                start_index,
-                remaining: BitMask(mask),
-                time_left: 26,
-            },
-            &mut cache,
-            &mut stats,
-        );
-        let sol_2 = solve(
-            &g,
-            Key {
-                start_index,
-                remaining: BitMask(inverse_mask),
-                time_left: 26,
-            },
-            &mut cache,
-            &mut stats,
-        );
+    let left = (actors - picked.len()) as u64;
+    for (k, &(pressure, mask)) in routes.iter().enumerate().skip(from) {
+        if total + pressure * left <= found.0 {
+            break;
+        }
+        if picked.iter().all(|&p| routes[p].1.is_disjoint(mask)) {
+            picked.push(k);
+            pick_disjoint(routes, actors, k, total + pressure, picked, found);
+            picked.pop();
+        }
+    }
+}
+
+fn main() {
+    // Usage: day_16 [--plan] [--minutes N] [--training T] [--actors K] [--start NAME] <input
+    // Part two gives K actors N - T minutes.
+    let mut show_plan = false;
+    let mut minutes = 30;
+    let mut training = 4;
+    let mut actors = 2;
+    let mut start = "AA".to_string();
+
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().unwrap_or_else(|| panic!("{arg} needs a value"));
+        match arg.as_str() {
+            "--plan" => show_plan = true,
+            "--minutes" => minutes = value().parse().unwrap(),
+            "--training" => training = value().parse().unwrap(),
+            "--actors" => actors = value().parse().unwrap(),
+            "--start" => start = value(),
+            _ => panic!("Unexpected argument {arg:?}!"),
+        }
+    }
+    assert!(
+        training <= minutes,
+        "Training can't take longer than the whole time"
+    );
+
+    let mut input = String::new();
+    stdin().read_to_string(&mut input).unwrap();
+    let g: Graph = input
+        .parse()
+        .unwrap_or_else(|e| panic!("Can't parse the valves {e}"));
+
+    let net = g
+        .network(&start)
+        .unwrap_or_else(|| panic!("There's no valve {start:?} to start from"));
 
-        ans_2 = ans_2.max(sol_1 + sol_2);
+    let mut cache: Cache = HashMap::new();
+    let mut stats = Stats::default();
+    let key_1 = Key {
+        start_index: net.start(),
+        remaining: BitMask::all_set(net.len()),
+        time_left: minutes,
+    };
+    let ans_1 = solve(&net, key_1.clone(), &mut cache, &mut stats);
+    println!("ans_1: {ans_1}");
 
-        processed_masks.insert(mask);
+    if show_plan {
+        let openings = plan(&net, key_1, &mut cache, &mut stats);
+        print_timeline(&g, &net, minutes, &openings);
     }
 
+    let time_limit = minutes - training;
+    let (ans_2, shares) = if dense_split_fits(net.len(), actors) {
+        let (ans, shares) = split_between(&best_per_subset(&net, time_limit), actors);
+        (ans, shares.into_iter().map(BitMask::from_index).collect())
+    } else {
+        split_sparse(&best_per_route(&net, time_limit), actors)
+    };
+
     println!("ans_2: {ans_2}");
+
+    if show_plan {
+        let mut openings = Vec::new();
+        for (actor, mask) in shares.into_iter().enumerate() {
+            let key = Key {
+                start_index: net.start(),
+                remaining: mask,
+                time_left: time_limit,
+            };
+            let share = plan(&net, key, &mut cache, &mut stats);
+            openings.extend(share.into_iter().map(|o| Opening { actor, ..o }));
+        }
+        print_timeline(&g, &net, time_limit, &openings);
+    }
 }
 
-fn gets() -> Option<String> {
-    let mut line = String::new();
-    let count = stdin().read_line(&mut line).unwrap();
-    if count > 0 {
-        Some(line.trim().to_string())
-    } else {
-        None
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EXAMPLE: &str = "Valve AA has flow rate=0; tunnels lead to valves DD, II, BB
+Valve BB has flow rate=13; tunnels lead to valves CC, AA
+Valve CC has flow rate=2; tunnels lead to valves DD, BB
+Valve DD has flow rate=20; tunnels lead to valves CC, AA, EE
+Valve EE has flow rate=3; tunnels lead to valves FF, DD
+Valve FF has flow rate=0; tunnels lead to valves EE, GG
+Valve GG has flow rate=0; tunnels lead to valves FF, HH
+Valve HH has flow rate=22; tunnel leads to valve GG
+Valve II has flow rate=0; tunnels lead to valves AA, JJ
+Valve JJ has flow rate=21; tunnel leads to valve II";
+
+    #[test]
+    fn sparse_split_matches_subset_table() {
+        let g: Graph = EXAMPLE.parse().unwrap();
+        let net = g.network("AA").unwrap();
+
+        for actors in 1..=3 {
+            let (dense, _) = split_between(&best_per_subset(&net, 26), actors);
+            let (sparse, shares) = split_sparse(&best_per_route(&net, 26), actors);
+            assert_eq!(sparse, dense, "{actors} actors");
+            assert_eq!(shares.len(), actors);
+        }
+        assert_eq!(split_sparse(&best_per_route(&net, 26), 2).0, 1707);
     }
 }
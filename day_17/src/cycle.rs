@@ -0,0 +1,46 @@
+use std::fmt::Display;
+
+/// Tower heights that start repeating after `start` figures: from then on
+/// every `len` figures add `height_gain` lines.
+#[derive(Debug, Clone)]
+pub struct Cycle {
+    pub start: usize,
+    pub len: usize,
+    pub height_gain: u64,
+    /// Height after each of the first `start + len` figures, starting with 0.
+    heights: Vec<u64>,
+}
+
+impl Cycle {
+    pub fn new(start: usize, heights: Vec<u64>) -> Self {
+        assert!(start + 1 < heights.len());
+        let end = heights.len() - 1;
+
+        Self {
+            start,
+            len: end - start,
+            height_gain: heights[end] - heights[start],
+            heights,
+        }
+    }
+
+    pub fn height_after(&self, figures_count: usize) -> u64 {
+        if figures_count < self.heights.len() {
+            return self.heights[figures_count];
+        }
+
+        let cycles = ((figures_count - self.start) / self.len) as u64;
+        let rest = (figures_count - self.start) % self.len;
+        self.heights[self.start + rest] + cycles * self.height_gain
+    }
+}
+
+impl Display for Cycle {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "starts after {} figures at height {}, repeats every {} figures, gaining {} lines",
+            self.start, self.heights[self.start], self.len, self.height_gain
+        )
+    }
+}
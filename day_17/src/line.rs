@@ -13,6 +13,10 @@ impl Line {
         Self::default()
     }
 
+    pub fn full() -> Self {
+        Self(Self::FULL_MASK)
+    }
+
     pub fn get(&self, i: usize) -> Pixel {
         let bit = ((self.0 >> i) & 1) == 1;
         bit.into()
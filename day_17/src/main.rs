@@ -3,6 +3,7 @@ use instr::Instr;
 use std::io::*;
 use tetris::Tetris;
 
+mod cycle;
 mod figure;
 mod instr;
 mod line;
@@ -11,6 +12,35 @@ mod pixel;
 mod tetris;
 
 fn main() {
+    // Usage: day_17 [--depth K] [FIGURES...] <input
+    let mut depth = Tetris::PROFILE_DEPTH;
+    let mut counts = Vec::<usize>::new();
+
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--depth" => depth = args.next().expect("--depth needs a value").parse().unwrap(),
+            _ => counts.push(arg.parse().expect("Figure counts must be integers")),
+        }
+    }
+
+    let tetris = puzzle_tetris(&gets().unwrap());
+
+    let cycle = tetris.find_cycle(depth);
+    println!("cycle: {cycle}");
+
+    if counts.is_empty() {
+        println!("solve (p1): {}", cycle.height_after(2022));
+        println!("solve (p2): {}", cycle.height_after(1000000000000));
+    } else {
+        for count in counts {
+            let height = cycle.height_after(count);
+            println!("height after {count} figures: {height}");
+        }
+    }
+}
+
+fn puzzle_tetris(jets: &str) -> Tetris {
     let figures = vec![
         Figure::new("####"),
         Figure::new(".#. ### .#."),
@@ -19,15 +49,12 @@ fn main() {
         Figure::new("## ##"),
     ];
 
-    let instructions: Vec<Instr> = gets().unwrap().bytes().map(Instr::parse).collect();
+    let instructions: Vec<Instr> = jets.bytes().map(Instr::parse).collect();
 
-    let tetris = Tetris {
+    Tetris {
         figures,
         instructions,
-    };
-
-    println!("solve (p1): {}", tetris.simulate(2022));
-    println!("solve (p2): {}", tetris.simulate(1000000000000));
+    }
 }
 
 fn gets() -> Option<String> {
@@ -39,3 +66,39 @@ fn gets() -> Option<String> {
         None
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EXAMPLE: &str = ">>><<><>><<<>><>>><<<>>><<<><<<>><>><<>>";
+
+    #[test]
+    fn example() {
+        let cycle = puzzle_tetris(EXAMPLE).find_cycle(Tetris::PROFILE_DEPTH);
+        assert_eq!(cycle.height_after(2022), 3068);
+        assert_eq!(cycle.height_after(1000000000000), 1514285714288);
+    }
+
+    #[test]
+    fn cycle_matches_direct_simulation() {
+        let tetris = puzzle_tetris(EXAMPLE);
+        let cycle = tetris.find_cycle(Tetris::PROFILE_DEPTH);
+
+        let edges = [
+            cycle.start,
+            cycle.start + cycle.len,
+            cycle.start + 3 * cycle.len,
+        ];
+        for count in edges
+            .into_iter()
+            .flat_map(|edge| edge.saturating_sub(2)..edge + 3)
+        {
+            assert_eq!(
+                cycle.height_after(count),
+                tetris.simulate(count),
+                "After {count} figures"
+            );
+        }
+    }
+}
@@ -1,18 +1,29 @@
 use std::collections::HashMap;
 
 use crate::line::Line;
-use crate::{figure::Figure, instr::Instr, loc::Loc, pixel::Pixel};
+use crate::{cycle::Cycle, figure::Figure, instr::Instr, loc::Loc, pixel::Pixel};
 
 pub struct Tetris {
     pub figures: Vec<Figure>,
     pub instructions: Vec<Instr>,
 }
 
-#[derive(Debug, Clone, Eq, PartialEq, Default, Hash)]
+#[derive(Debug, Clone, Eq, PartialEq, Default)]
 struct State {
     fig_index: usize,
     instr_index: usize,
     field: Vec<Line>,
+    /// How far below the top any figure has come to rest so far.
+    deepest_rest: u64,
+}
+
+/// Everything that decides how the next figures fall, as long as none of them
+/// gets below the profile.
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+struct Key {
+    fig_index: usize,
+    instr_index: usize,
+    profile: Vec<Line>,
 }
 
 impl Tetris {
@@ -20,47 +31,43 @@ impl Tetris {
     const START_X: u64 = 2;
     const START_Y: u64 = 3;
 
-    pub fn simulate(&self, mut figures_count: usize) -> u64 {
-        let cycle_len = self.optimal_step_count();
-
-        let mut ans: u64 = 0;
-        let mut state = State::default();
-        let mut cache = HashMap::<State, (u64, State)>::new();
-
-        while figures_count > 0 {
-            if figures_count >= cycle_len {
-                ans += if let Some((increment, next_state)) = cache.get(&state) {
-                    state = next_state.clone();
-                    *increment
-                } else {
-                    let initial_state = state.clone();
-                    let increment = self.simulate_direct(&mut state, cycle_len);
-                    cache.insert(initial_state, (increment, state.clone()));
-                    increment
-                };
-
-                figures_count -= cycle_len;
-            } else {
-                ans += self.simulate_direct(&mut state, figures_count);
-                figures_count = 0;
-            }
-        }
+    /// Default number of top lines that make up the surface profile.
+    pub const PROFILE_DEPTH: usize = 64;
 
-        ans
+    /// Straight simulation without looking for cycles.
+    #[cfg(test)]
+    pub fn simulate(&self, figures_count: usize) -> u64 {
+        self.simulate_direct(&mut State::default(), figures_count)
     }
 
-    fn optimal_step_count(&self) -> usize {
-        let f = self.figures.len();
-        let i = self.instructions.len();
+    /// Drops figures one at a time until the next figure, the next jet and
+    /// the top `depth` lines of the field are the same as once before.
+    pub fn find_cycle(&self, depth: usize) -> Cycle {
+        let mut state = State::default();
+        let mut heights = vec![0];
+        let mut seen = HashMap::<Key, usize>::new();
+
+        loop {
+            let key = Key {
+                fig_index: state.fig_index,
+                instr_index: state.instr_index,
+                profile: state.profile(depth),
+            };
 
-        let lcm = num::integer::lcm(f, i);
+            let count = heights.len() - 1;
+            if let Some(&start) = seen.get(&key) {
+                assert!(
+                    state.deepest_rest < depth as u64,
+                    "A figure came to rest {} lines below the top, the profile of {depth} lines is too shallow",
+                    state.deepest_rest
+                );
+                return Cycle::new(start, heights);
+            }
+            seen.insert(key, count);
 
-        let mut ret = lcm;
-        while ret < 100_000 {
-            ret += lcm;
+            let height = heights[count] + self.simulate_direct(&mut state, 1);
+            heights.push(height);
         }
-
-        ret
     }
 
     fn simulate_direct(&self, state: &mut State, figures_count: usize) -> u64 {
@@ -94,6 +101,9 @@ impl Tetris {
                 }
             }
 
+            let depth = state.height().saturating_sub(pos.i);
+            state.deepest_rest = state.deepest_rest.max(depth);
+
             state.petrify(pos, figure);
             state.optimize(figure.n as u64, &mut removed_lines);
         }
@@ -108,6 +118,14 @@ impl State {
         self.field.len() as u64
     }
 
+    /// The top `depth` lines, from the top down. Lines that have been cut off
+    /// count as full, since nothing can get past them anyway.
+    fn profile(&self, depth: usize) -> Vec<Line> {
+        let mut profile: Vec<Line> = self.field.iter().rev().take(depth).copied().collect();
+        profile.resize(depth, Line::full());
+        profile
+    }
+
     fn fits(&self, pos: Loc, figure: &Figure) -> bool {
         for i in 0..figure.n {
             for j in 0..figure.m {